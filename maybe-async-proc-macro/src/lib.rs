@@ -10,9 +10,9 @@ use syn::{
     punctuated::Punctuated,
     spanned::Spanned,
     token::Comma,
-    visit_mut::{visit_expr_mut, VisitMut},
-    ConstParam, Error, Expr, GenericParam, Ident, Item, ItemFn, PathArguments, ReturnType, Stmt,
-    Token,
+    visit_mut::{visit_expr_mut, visit_generic_argument_mut, VisitMut},
+    ConstParam, Error, Expr, GenericArgument, GenericParam, Ident, Item, ItemFn, PathArguments,
+    ReturnType, Stmt, Token, Type,
 };
 
 #[derive(Debug, Eq)]
@@ -148,6 +148,26 @@ fn maybe_fn(mut item: ItemFn, kinds: Vec<Keyword>) -> TokenStream {
     item.sig.generics.lt_token.get_or_insert_default();
     item.sig.generics.gt_token.get_or_insert_default();
     let mod_name = &item.sig.ident;
+    // The function's own generics are forwarded to the helper trait, ahead of the effect.
+    let mut params = item.sig.generics.params.clone();
+    if !params.empty_or_trailing() {
+        params.push_punct(Default::default());
+    }
+    let mut generic_args: Punctuated<_, Comma> = item
+        .sig
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            GenericParam::Type(ty) => ty.ident.to_token_stream(),
+            GenericParam::Lifetime(lt) => lt.lifetime.to_token_stream(),
+            GenericParam::Const(c) => c.ident.to_token_stream(),
+        })
+        .collect();
+    if !generic_args.empty_or_trailing() {
+        generic_args.push_punct(Default::default());
+    }
+    let where_clause = item.sig.generics.where_clause.clone();
     let effect_param = parse_quote!(const EFFECT: maybe_async_std::prelude::Effects);
     item.sig
         .generics
@@ -170,37 +190,61 @@ fn maybe_fn(mut item: ItemFn, kinds: Vec<Keyword>) -> TokenStream {
             },
         })
         .collect();
-    let ret = match &item.sig.output {
-        ReturnType::Default => quote!(()),
-        ReturnType::Type(_, t) => quote!(#t),
+    let mut ret: Type = match &item.sig.output {
+        ReturnType::Default => parse_quote!(()),
+        ReturnType::Type(_, t) => (**t).clone(),
     };
-    item.sig.output = parse_quote!(-> <() as #mod_name::Helper<EFFECT>>::Ret);
+    item.sig.output = parse_quote!(-> <() as #mod_name::Helper<#generic_args EFFECT>>::Ret);
+    // Only the effects the function was written for have an impl, so calling
+    // it with any other effect is a type error rather than a runtime one.
+    item.sig
+        .generics
+        .make_where_clause()
+        .predicates
+        .push(parse_quote!((): #mod_name::Helper<#generic_args EFFECT>));
 
-    let body = parse_quote!({<() as #mod_name::Helper<EFFECT>>::act(#call_args)});
+    let body = parse_quote!({<() as #mod_name::Helper<#generic_args EFFECT>>::act(#call_args)});
 
     let body = std::mem::replace(&mut *item.block, body);
 
     let (mut body, effect_bodies) = split_if_expression(body, &kinds);
 
-    let effect_bodies = kinds.iter().zip(effect_bodies).map(|(effect, mut body)| {
-        Effectifier(&effect, &kinds).visit_expr_mut(&mut body);
-        let effect_name = effect.identify(effect.all_caps_name());
-        let ret = match &effect.kind {
-            KeywordKind::Async => quote!(impl std::future::Future<Output = #ret>),
-            KeywordKind::Try => quote!(#ret),
-        };
-        let effect = quote!(Effects::#effect_name);
-        quote! {
-            impl Helper<{#effect}> for () {
-                type Ret = #ret;
-                fn act(#args) -> Self::Ret {
-                    #body
+    let effect_bodies = kinds
+        .iter()
+        .zip(effect_bodies)
+        .map(|(effect, mut body)| {
+            Effectifier(&effect, &kinds).visit_expr_mut(&mut body);
+            let effect_name = effect.identify(effect.all_caps_name());
+            let effect_path = quote!(Effects::#effect_name);
+            let (mut args, mut ret) = (args.clone(), ret.clone());
+            let mut subst = EffectSubstituter(parse_quote!(#effect_path));
+            args.iter_mut().for_each(|arg| subst.visit_fn_arg_mut(arg));
+            subst.visit_type_mut(&mut ret);
+            subst.visit_expr_mut(&mut body);
+            let ret = match &effect.kind {
+                KeywordKind::Async => quote!(impl std::future::Future<Output = #ret>),
+                KeywordKind::Try => quote!(#ret),
+            };
+            quote! {
+                impl<#params> Helper<#generic_args {#effect_path}> for () #where_clause {
+                    type Ret = #ret;
+                    fn act(#args) -> Self::Ret {
+                        #body
+                    }
                 }
             }
-        }
-    });
+        })
+        .collect::<Vec<_>>();
     DeEffectifier(&kinds).visit_block_mut(&mut body);
 
+    let mut subst = EffectSubstituter(parse_quote!(Effects::NONE));
+    let mut sync_args = args.clone();
+    sync_args
+        .iter_mut()
+        .for_each(|arg| subst.visit_fn_arg_mut(arg));
+    subst.visit_type_mut(&mut ret);
+    subst.visit_block_mut(&mut body);
+
     assert_eq!(kinds.len(), 1);
     let ret = match kinds[0].kind {
         KeywordKind::Async => quote!(#ret),
@@ -212,28 +256,18 @@ fn maybe_fn(mut item: ItemFn, kinds: Vec<Keyword>) -> TokenStream {
         pub mod #mod_name {
             use super::*;
             use maybe_async_std::prelude::Effects;
-            pub trait Helper<const EFFECT: Effects> {
+            pub trait Helper<#params const EFFECT: Effects> #where_clause {
                 type Ret;
                 fn act(#args) -> Self::Ret;
             }
 
             #(#effect_bodies)*
 
-            impl Helper<{Effects::NONE}> for () {
+            impl<#params> Helper<#generic_args {Effects::NONE}> for () #where_clause {
                 type Ret = #ret;
-                fn act(#args) -> Self::Ret
+                fn act(#sync_args) -> Self::Ret
                     #body
             }
-
-            // Actually only an impl for `MaybeAsync<NONE>`, as there are only two possible impls
-            // and we wrote both of them. Workaround for https://github.com/rust-lang/rust/pull/104803
-            impl<const EFFECT: Effects> Helper<EFFECT> for () {
-                default type Ret = ();
-                #[allow(unused_variables)]
-                default fn act(#args) -> Self::Ret {
-                    panic!("your trait solver is broken")
-                }
-            }
         }
     };
     TokenStream::from(expanded)
@@ -279,6 +313,10 @@ struct Effectifier<'a>(&'a Keyword, &'a [Keyword]);
 impl VisitMut for Effectifier<'_> {
     fn visit_expr_mut(&mut self, e: &mut Expr) {
         let muta = |expr: &mut _, kind: Ident| {
+            // Methods get their effect from the type of their receiver.
+            if let Expr::MethodCall(_) = expr {
+                return;
+            }
             if let Expr::Call(call) = expr {
                 if let Expr::Path(path) = &mut *call.func {
                     let last = path.path.segments.last_mut().unwrap();
@@ -315,11 +353,41 @@ impl VisitMut for Effectifier<'_> {
     }
 }
 
+/// Replaces mentions of `EFFECT` with the effect a helper impl is for, so that
+/// signatures and bodies can name types like `Sender<T, EFFECT>`.
+struct EffectSubstituter(Expr);
+
+impl VisitMut for EffectSubstituter {
+    fn visit_generic_argument_mut(&mut self, arg: &mut GenericArgument) {
+        if let GenericArgument::Type(Type::Path(ty)) = arg {
+            if ty.qself.is_none() && ty.path.is_ident("EFFECT") {
+                let effect = &self.0;
+                *arg = GenericArgument::Const(parse_quote!({ #effect }));
+                return;
+            }
+        }
+        visit_generic_argument_mut(self, arg)
+    }
+
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        if let Expr::Path(path) = e {
+            if path.qself.is_none() && path.path.is_ident("EFFECT") {
+                *e = self.0.clone();
+                return;
+            }
+        }
+        visit_expr_mut(self, e)
+    }
+}
+
 struct DeEffectifier<'a>(&'a [Keyword]);
 
 impl VisitMut for DeEffectifier<'_> {
     fn visit_expr_mut(&mut self, e: &mut Expr) {
         let muta = |mut inner| {
+            if let Expr::MethodCall(_) = &inner {
+                return inner;
+            }
             if let Expr::Call(call) = &mut inner {
                 if let Expr::Path(path) = &mut *call.func {
                     let last = path.path.segments.last_mut().unwrap();
@@ -352,6 +420,7 @@ impl VisitMut for DeEffectifier<'_> {
                 }
             }
         }
+        visit_expr_mut(self, e)
     }
 }
 
@@ -1,5 +1,4 @@
 #![feature(type_alias_impl_trait)]
-#![feature(specialization)]
 #![feature(associated_type_defaults)]
 #![feature(async_iterator)]
 #![feature(adt_const_params)]
@@ -12,7 +11,9 @@ use std::{async_iter::AsyncIterator, future::Future};
 use maybe_async_proc_macro::maybe;
 
 pub mod prelude {
-    #[derive(PartialEq, Eq)]
+    use std::marker::ConstParamTy;
+
+    #[derive(PartialEq, Eq, ConstParamTy)]
     pub struct Effects {
         pub async_: bool,
         pub try_: bool,
@@ -24,8 +25,8 @@ pub mod prelude {
             try_: false,
         };
         pub const TRY: Self = Self {
-            async_: true,
-            try_: false,
+            async_: false,
+            try_: true,
         };
         pub const NONE: Self = Self {
             async_: false,
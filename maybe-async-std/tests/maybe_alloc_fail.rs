@@ -1,5 +1,4 @@
 #![feature(const_waker, type_alias_impl_trait)]
-#![feature(specialization)]
 #![feature(try_blocks)]
 #![feature(try_trait_v2)]
 #![feature(adt_const_params)]
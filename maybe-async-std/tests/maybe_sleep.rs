@@ -1,5 +1,4 @@
 #![feature(const_waker, type_alias_impl_trait)]
-#![feature(specialization)]
 #![feature(adt_const_params)]
 #![allow(incomplete_features)]
 
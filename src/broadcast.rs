@@ -0,0 +1,274 @@
+//! A channel where every receiver sees every message.
+//!
+//! The channel keeps the last `cap` messages around. Sending never blocks: if a
+//! receiver falls further behind than that, the oldest messages are dropped and
+//! its next `recv` reports how many it missed with [`RecvError::Lagged`].
+//!
+//! Receiving clones the message while the channel is locked, so the `Clone`
+//! impl of a message must not use the channel it's sent on, or it deadlocks.
+
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use event_listener::Event;
+use maybe_async_proc_macro::maybe;
+
+use crate::{listen, Effects, SendError};
+
+/// Creates a broadcast channel.
+///
+/// The created channel holds on to at most `cap` messages at a time.
+///
+/// # Panics
+///
+/// Panics if `cap` is zero.
+pub fn broadcast<T: Clone, const EFFECT: Effects>(
+    cap: usize,
+) -> (Sender<T, EFFECT>, Receiver<T, EFFECT>) {
+    assert!(cap > 0, "capacity cannot be zero");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            queue: VecDeque::with_capacity(cap),
+            cap,
+            head: 0,
+            closed: false,
+            senders: 1,
+            receivers: 1,
+        }),
+        recv_ops: Event::new(),
+    });
+    let receiver = Receiver {
+        shared: shared.clone(),
+        next: AtomicU64::new(0),
+    };
+    (Sender { shared }, receiver)
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    /// Notified when a message is sent or the channel closes.
+    recv_ops: Event,
+}
+
+impl<T> Shared<T> {
+    fn state(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    cap: usize,
+    /// The position of the oldest message still in `queue`.
+    head: u64,
+    closed: bool,
+    senders: usize,
+    receivers: usize,
+}
+
+impl<T> State<T> {
+    /// The position the next message will be sent at.
+    fn tail(&self) -> u64 {
+        self.head + self.queue.len() as u64
+    }
+}
+
+/// The sending side of a broadcast channel.
+pub struct Sender<T, const EFFECT: Effects> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T, const EFFECT: Effects> Sender<T, EFFECT> {
+    /// Send an item to every receiver.
+    ///
+    /// This never blocks. If the channel is full, the oldest message is dropped
+    /// to make room. Fails if there are no receivers. This never allocates
+    /// either, as room for `cap` messages is made when the channel is created.
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        let mut state = self.shared.state();
+        if state.receivers == 0 {
+            return Err(SendError(t));
+        }
+        let mut oldest = None;
+        if state.queue.len() == state.cap {
+            oldest = state.queue.pop_front();
+            state.head += 1;
+        }
+        state.queue.push_back(t);
+        drop(state);
+        // Dropping the oldest message can run arbitrary code, so not while locked.
+        drop(oldest);
+        self.shared.recv_ops.notify(usize::MAX);
+        Ok(())
+    }
+
+    /// Creates a new receiver, which sees every message sent from now on.
+    pub fn subscribe(&self) -> Receiver<T, EFFECT> {
+        let mut state = self.shared.state();
+        state.receivers += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            next: AtomicU64::new(state.tail()),
+        }
+    }
+
+    /// Returns the number of receivers for the channel.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.state().receivers
+    }
+}
+
+impl<T, const EFFECT: Effects> Clone for Sender<T, EFFECT> {
+    fn clone(&self) -> Self {
+        self.shared.state().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T, const EFFECT: Effects> Drop for Sender<T, EFFECT> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state();
+        state.senders -= 1;
+        if state.senders == 0 {
+            state.closed = true;
+            drop(state);
+            self.shared.recv_ops.notify(usize::MAX);
+        }
+    }
+}
+
+/// The receiving side of a broadcast channel.
+pub struct Receiver<T, const EFFECT: Effects> {
+    shared: Arc<Shared<T>>,
+    /// The position of the next message this receiver will see.
+    next: AtomicU64,
+}
+
+impl<T: Clone, const EFFECT: Effects> Receiver<T, EFFECT> {
+    /// Receive the next item from the channel
+    pub fn recv<'a>(&'a self) -> <() as receiver::recv::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): receiver::recv::Helper<'a, T, EFFECT>,
+    {
+        receiver::recv::<T, EFFECT>(self)
+    }
+
+    /// Attempts to receive the next item from the channel without blocking.
+    ///
+    /// The item is cloned while the channel is locked, so its `Clone` impl
+    /// must not use this channel.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let state = self.shared.state();
+        let next = self.next.load(Ordering::SeqCst);
+        if next < state.head {
+            self.next.store(state.head, Ordering::SeqCst);
+            return Err(TryRecvError::Lagged(state.head - next));
+        }
+        match state.queue.get((next - state.head) as usize) {
+            Some(t) => {
+                self.next.store(next + 1, Ordering::SeqCst);
+                Ok(t.clone())
+            }
+            None if state.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Receives the next item, or returns `None` if there's nothing to report yet.
+    fn try_recv_ready(&self) -> Option<Result<T, RecvError>> {
+        match self.try_recv() {
+            Ok(t) => Some(Ok(t)),
+            Err(TryRecvError::Lagged(n)) => Some(Err(RecvError::Lagged(n))),
+            Err(TryRecvError::Disconnected) => Some(Err(RecvError::Disconnected)),
+            Err(TryRecvError::Empty) => None,
+        }
+    }
+
+    fn recv_blocking(&self) -> Result<T, RecvError> {
+        let received = listen::wait_blocking(&self.shared.recv_ops, None, || self.try_recv_ready());
+        received.expect("waited without a deadline")
+    }
+
+    async fn recv_async(&self) -> Result<T, RecvError> {
+        let received = listen::wait_async(&self.shared.recv_ops, None, || self.try_recv_ready());
+        received.await.expect("waited without a deadline")
+    }
+}
+
+impl<T, const EFFECT: Effects> Drop for Receiver<T, EFFECT> {
+    fn drop(&mut self) {
+        self.shared.state().receivers -= 1;
+    }
+}
+
+mod receiver {
+    use super::*;
+
+    #[maybe(async)]
+    pub(crate) fn recv<'a, T: Clone + 'a>(
+        receiver: &'a Receiver<T, EFFECT>,
+    ) -> Result<T, RecvError> {
+        if ASYNC {
+            async move { receiver.recv_async().await }
+        } else {
+            receiver.recv_blocking()
+        }
+    }
+
+    impl<'a, T: Clone + 'a> recv::Helper<'a, T, { Effects::TRY }> for () {
+        type Ret = Result<T, RecvError>;
+        fn act(receiver: &'a Receiver<T, { Effects::TRY }>) -> Self::Ret {
+            receiver.recv_blocking()
+        }
+    }
+}
+
+/// An error returned from the broadcast `recv` method.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvError {
+    /// The receiver fell behind, and this many messages were dropped before it
+    /// could see them. The next `recv` continues with the oldest message left.
+    Lagged(u64),
+    /// A message could not be received because the channel is empty and disconnected.
+    Disconnected,
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvError::Lagged(n) => write!(f, "receiver lagged behind by {n} messages"),
+            RecvError::Disconnected => "receiving on an empty and disconnected channel".fmt(f),
+        }
+    }
+}
+
+impl error::Error for RecvError {}
+
+/// An error returned from the broadcast `try_recv` method.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TryRecvError {
+    /// A message could not be received because the channel is empty.
+    Empty,
+    /// The receiver fell behind, and this many messages were dropped before it
+    /// could see them. The next `try_recv` continues with the oldest message left.
+    Lagged(u64),
+    /// A message could not be received because the channel is empty and disconnected.
+    Disconnected,
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => "receiving on an empty channel".fmt(f),
+            TryRecvError::Lagged(n) => write!(f, "receiver lagged behind by {n} messages"),
+            TryRecvError::Disconnected => "receiving on an empty and disconnected channel".fmt(f),
+        }
+    }
+}
+
+impl error::Error for TryRecvError {}
@@ -0,0 +1,924 @@
+//! The queue behind every channel.
+//!
+//! Both halves of a channel share one [`Channel`] whatever mode each of them is
+//! in, so a blocking send wakes an async receive and the other way round.
+
+use std::alloc::AllocError;
+use std::collections::{BinaryHeap, VecDeque};
+use std::convert::Infallible;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use event_listener::{Event, EventListener};
+
+use crate::{listen, time};
+use crate::{
+    OverflowPolicy, RecvTimeoutError, SendAllocError, SendError, SendTimeoutError, TryRecvError,
+    TrySendError,
+};
+
+/// How many messages `send_all` takes from its iterator at a time.
+///
+/// Messages are moved out of the iterator before taking the lock, as it may run
+/// any code, including code using this channel. Keeping batches small bounds
+/// how far ahead of the channel that reads, which matters for long or endless
+/// iterators, and how much memory it takes.
+const BATCH: usize = 32;
+
+/// Creates a channel holding at most `cap` messages, or any number of them if
+/// `cap` is `None`.
+pub(crate) fn channel<T>(cap: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    with_queue(Queue::Fifo(VecDeque::new()), cap, OverflowPolicy::Block)
+}
+
+/// Like `channel`, but messages sent while it's full are handled as `overflow` says.
+pub(crate) fn channel_with_policy<T>(
+    cap: usize,
+    overflow: OverflowPolicy,
+) -> (Sender<T>, Receiver<T>) {
+    with_queue(Queue::Fifo(VecDeque::new()), Some(cap), overflow)
+}
+
+/// Like `channel`, but messages are received greatest first.
+pub(crate) fn priority_channel<T: Ord>(cap: Option<usize>) -> (Sender<T>, Receiver<T>) {
+    let queue = Queue::Priority {
+        heap: BinaryHeap::new(),
+        push: BinaryHeap::push,
+        pop: BinaryHeap::pop,
+    };
+    with_queue(queue, cap, OverflowPolicy::Block)
+}
+
+fn with_queue<T>(
+    mut queue: Queue<T>,
+    cap: Option<usize>,
+    overflow: OverflowPolicy,
+) -> (Sender<T>, Receiver<T>) {
+    let rendezvous = cap == Some(0);
+    // Rendezvous and oneshot channels hold at most one message, whether it's
+    // buffered or in passing, so make room for it up front and never allocate
+    // while sending.
+    if cap.is_some_and(|cap| cap <= 1) {
+        queue.reserve(1);
+    }
+    let chan = Arc::new(Channel {
+        state: Mutex::new(State {
+            queue,
+            cap,
+            closed: false,
+            waiting_receivers: 0,
+            taken: 0,
+            dropped: 0,
+        }),
+        rendezvous,
+        overflow,
+        send_ops: Event::new(),
+        recv_ops: Event::new(),
+        sender_count: AtomicUsize::new(1),
+        receiver_count: AtomicUsize::new(1),
+    });
+    let sender = Sender {
+        chan: chan.clone(),
+        #[cfg(feature = "futures")]
+        sink: Default::default(),
+    };
+    let receiver = Receiver {
+        chan,
+        listener: None,
+    };
+    (sender, receiver)
+}
+
+pub(crate) struct Channel<T> {
+    state: Mutex<State<T>>,
+    /// Notified when a message leaves the channel, or the channel closes.
+    pub(crate) send_ops: Event,
+    /// Notified when a message enters the channel, or the channel closes.
+    pub(crate) recv_ops: Event,
+    /// Whether the channel has zero capacity, which never changes.
+    rendezvous: bool,
+    overflow: OverflowPolicy,
+    sender_count: AtomicUsize,
+    receiver_count: AtomicUsize,
+}
+
+/// The messages in a channel, in the order they'll be received.
+enum Queue<T> {
+    Fifo(VecDeque<T>),
+    /// `BinaryHeap` only works for `T: Ord`, so we get its methods from whoever
+    /// created the channel, where that's known.
+    Priority {
+        heap: BinaryHeap<T>,
+        push: fn(&mut BinaryHeap<T>, T),
+        pop: fn(&mut BinaryHeap<T>) -> Option<T>,
+    },
+}
+
+impl<T> Queue<T> {
+    fn push(&mut self, msg: T) {
+        match self {
+            Queue::Fifo(queue) => queue.push_back(msg),
+            Queue::Priority { heap, push, .. } => push(heap, msg),
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        match self {
+            Queue::Fifo(queue) => queue.reserve(additional),
+            Queue::Priority { heap, .. } => heap.reserve(additional),
+        }
+    }
+
+    /// Makes room for `additional` more messages, failing instead of aborting
+    /// if that takes memory which can't be allocated.
+    fn try_reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        let res = match self {
+            Queue::Fifo(queue) => queue.try_reserve(additional),
+            Queue::Priority { heap, .. } => heap.try_reserve(additional),
+        };
+        res.map_err(|_| AllocError)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        match self {
+            Queue::Fifo(queue) => queue.pop_front(),
+            Queue::Priority { heap, pop, .. } => pop(heap),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Queue::Fifo(queue) => queue.len(),
+            Queue::Priority { heap, .. } => heap.len(),
+        }
+    }
+}
+
+struct State<T> {
+    queue: Queue<T>,
+    cap: Option<usize>,
+    closed: bool,
+    /// Receivers blocked in `recv_blocking` on an empty channel. Unlike async
+    /// and select receives, these can't give up without taking a message
+    /// handed to them, so a rendezvous `try_send` can count on them.
+    waiting_receivers: usize,
+    /// Messages received so far, which is how a rendezvous sender can tell
+    /// whether its message has been taken.
+    taken: u64,
+    /// Messages dropped by the overflow policy so far.
+    dropped: usize,
+}
+
+impl<T> State<T> {
+    /// How many more messages fit in the channel right now.
+    fn room(&self) -> usize {
+        match self.cap {
+            // Without a buffer, a message only fits if a receiver is waiting to
+            // take it, and nothing else is being handed over already.
+            Some(0) => usize::from(self.waiting_receivers > 0 && self.queue.len() == 0),
+            Some(cap) => cap.saturating_sub(self.queue.len()),
+            None => usize::MAX,
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.room() == 0
+    }
+
+    /// Sends `msg` on a full channel by dropping a message, if `overflow`
+    /// allows. Returns the dropped message, or gives `msg` back if it has to
+    /// wait instead.
+    fn push_overflowing(&mut self, overflow: OverflowPolicy, msg: T) -> Result<T, T> {
+        let dropped = match overflow {
+            OverflowPolicy::Block | OverflowPolicy::Error => return Err(msg),
+            OverflowPolicy::DropNewest => msg,
+            OverflowPolicy::DropOldest => {
+                let oldest = self.queue.pop().unwrap();
+                self.queue.push(msg);
+                oldest
+            }
+        };
+        self.dropped += 1;
+        Ok(dropped)
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        let msg = self.queue.pop()?;
+        self.taken += 1;
+        Some(msg)
+    }
+}
+
+impl<T> Channel<T> {
+    fn state(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap()
+    }
+
+    /// Lets receivers know `n` messages entered the channel.
+    fn notify_receivers(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        // A message handed over by `try_send` has to reach the blocked receiver
+        // it counted on, so don't risk a single wakeup going to someone else.
+        match self.rendezvous {
+            true => self.recv_ops.notify(usize::MAX),
+            false => self.recv_ops.notify_additional(n),
+        };
+    }
+
+    /// Lets senders know `n` messages left the channel.
+    fn notify_senders(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        // Rendezvous senders wait either for their own message to be taken or
+        // for the previous one to be out of the way, so wake all of them.
+        match self.rendezvous {
+            true => self.send_ops.notify(usize::MAX),
+            false => self.send_ops.notify_additional(n),
+        };
+    }
+
+    /// Offers `msg` to receivers of a rendezvous channel, as long as nothing
+    /// else is being handed over. Returns a ticket to check on the offer with,
+    /// or gives `msg` back if it couldn't be offered, which it never can on a
+    /// channel with a buffer.
+    pub(crate) fn offer(&self, msg: T) -> Result<u64, T> {
+        if !self.rendezvous {
+            return Err(msg);
+        }
+        let mut state = self.state();
+        if state.closed || state.queue.len() > 0 {
+            return Err(msg);
+        }
+        state.queue.push(msg);
+        let ticket = state.taken;
+        drop(state);
+        self.notify_receivers(1);
+        Ok(ticket)
+    }
+
+    /// Returns whether the offer with `ticket` has either been taken or won't
+    /// be because the channel closed. `retract` tells which.
+    pub(crate) fn settled(&self, ticket: u64) -> bool {
+        let state = self.state();
+        state.taken > ticket || state.closed
+    }
+
+    /// Takes the offer with `ticket` back, unless a receiver already took it.
+    pub(crate) fn retract(&self, ticket: u64) -> Result<(), T> {
+        let mut state = self.state();
+        if state.taken > ticket {
+            return Ok(());
+        }
+        // Nothing else can be offered until this one is out of the way, so the
+        // queue holds just this message.
+        let msg = state.queue.pop().unwrap();
+        drop(state);
+        self.notify_senders(1);
+        Err(msg)
+    }
+
+    pub(crate) fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        self.try_send_overflowing(msg).map(drop)
+    }
+
+    /// Like `try_send`, but returns the message the overflow policy dropped to
+    /// make room, if any.
+    pub(crate) fn try_send_overflowing(&self, msg: T) -> Result<Option<T>, TrySendError<T>> {
+        let Ok(res) = self.try_send_with(msg, |_, msg| Ok::<_, Infallible>(msg));
+        res
+    }
+
+    /// Like `try_send`, but gives `msg` back in a `SendAllocError` instead of
+    /// aborting if the queue can't grow to fit it.
+    pub(crate) fn try_send_fallible(
+        &self,
+        msg: T,
+    ) -> Result<Result<(), TrySendError<T>>, SendAllocError<T>> {
+        Ok(self.try_send_overflowing_fallible(msg)?.map(drop))
+    }
+
+    /// Like `try_send_overflowing`, but gives `msg` back in a `SendAllocError`
+    /// instead of aborting if the queue can't grow to fit it.
+    pub(crate) fn try_send_overflowing_fallible(
+        &self,
+        msg: T,
+    ) -> Result<Result<Option<T>, TrySendError<T>>, SendAllocError<T>> {
+        self.try_send_with(msg, |queue, msg| match queue.try_reserve(1) {
+            Ok(()) => Ok(msg),
+            Err(AllocError) => Err(SendAllocError(msg)),
+        })
+    }
+
+    /// `reserve` makes room for `msg` in the queue, or fails with it.
+    fn try_send_with<E>(
+        &self,
+        msg: T,
+        reserve: impl FnOnce(&mut Queue<T>, T) -> Result<T, E>,
+    ) -> Result<Result<Option<T>, TrySendError<T>>, E> {
+        let mut state = self.state();
+        if state.closed {
+            return Ok(Err(TrySendError::Disconnected(msg)));
+        }
+        if state.is_full() {
+            // Hand a dropped message back so that it's only dropped once the
+            // lock is released, in case its destructor uses this channel.
+            let res = state.push_overflowing(self.overflow, msg);
+            return Ok(res.map(Some).map_err(TrySendError::Full));
+        }
+        let msg = reserve(&mut state.queue, msg)?;
+        state.queue.push(msg);
+        drop(state);
+        self.notify_receivers(1);
+        Ok(Ok(None))
+    }
+
+    /// Sends `msg` as the overflow policy says, returning the message it dropped
+    /// to make room, if any. Only `Block` waits for room, and only `Error` fails
+    /// with `Full`.
+    pub(crate) fn send_overflowing_blocking(&self, msg: T) -> Result<Option<T>, TrySendError<T>> {
+        match self.overflow {
+            OverflowPolicy::Block => match self.send_blocking(msg, None) {
+                Ok(()) => Ok(None),
+                Err(err) => Err(TrySendError::Disconnected(err.into_inner())),
+            },
+            _ => self.try_send_overflowing(msg),
+        }
+    }
+
+    /// Like `send_overflowing_blocking`, but gives `msg` back in a
+    /// `SendAllocError` instead of aborting if the queue can't grow to fit it.
+    pub(crate) fn send_overflowing_fallible(
+        &self,
+        msg: T,
+    ) -> Result<Result<Option<T>, TrySendError<T>>, SendAllocError<T>> {
+        match self.overflow {
+            OverflowPolicy::Block => match self.send_blocking_fallible(msg, None)? {
+                Ok(()) => Ok(Ok(None)),
+                Err(err) => Ok(Err(TrySendError::Disconnected(err.into_inner()))),
+            },
+            _ => self.try_send_overflowing_fallible(msg),
+        }
+    }
+
+    /// Like `send_overflowing_blocking`, but waits for room asynchronously.
+    pub(crate) async fn send_overflowing_async(
+        &self,
+        msg: T,
+    ) -> Result<Option<T>, TrySendError<T>> {
+        match self.overflow {
+            OverflowPolicy::Block => match self.send_async(msg, None).await {
+                Ok(()) => Ok(None),
+                Err(err) => Err(TrySendError::Disconnected(err.into_inner())),
+            },
+            _ => self.try_send_overflowing(msg),
+        }
+    }
+
+    /// Sends a message, blocking until there's room, the channel closes or
+    /// `deadline` passes.
+    pub(crate) fn send_blocking(
+        &self,
+        msg: T,
+        deadline: Option<Instant>,
+    ) -> Result<(), SendTimeoutError<T>> {
+        let Ok(res) =
+            self.send_blocking_with(msg, deadline, |msg| Ok::<_, Infallible>(self.try_send(msg)));
+        res
+    }
+
+    /// Like `send_blocking`, but gives `msg` back in a `SendAllocError` instead
+    /// of aborting if the queue can't grow to fit it.
+    pub(crate) fn send_blocking_fallible(
+        &self,
+        msg: T,
+        deadline: Option<Instant>,
+    ) -> Result<Result<(), SendTimeoutError<T>>, SendAllocError<T>> {
+        self.send_blocking_with(msg, deadline, |msg| self.try_send_fallible(msg))
+    }
+
+    fn send_blocking_with<E>(
+        &self,
+        msg: T,
+        deadline: Option<Instant>,
+        try_send: impl Fn(T) -> Result<Result<(), TrySendError<T>>, E>,
+    ) -> Result<Result<(), SendTimeoutError<T>>, E> {
+        let mut msg = Some(msg);
+        let sent = listen::wait_blocking(&self.send_ops, deadline, || {
+            let t = match try_send(msg.take().unwrap()) {
+                Ok(Ok(())) => return Some(Ok(Ok(()))),
+                Ok(Err(TrySendError::Disconnected(t))) => {
+                    return Some(Ok(Err(SendTimeoutError::Disconnected(t))))
+                }
+                Ok(Err(TrySendError::Full(t))) => t,
+                Err(err) => return Some(Err(err)),
+            };
+            // Without a buffer, wait for somebody to take it instead.
+            match self.offer(t) {
+                Ok(ticket) => Some(Ok(self.wait_taken_blocking(ticket, deadline))),
+                Err(t) => {
+                    msg = Some(t);
+                    None
+                }
+            }
+        });
+        sent.unwrap_or_else(|| Ok(Err(SendTimeoutError::Timeout(msg.unwrap()))))
+    }
+
+    /// Blocks until the offer with `ticket` is taken, taking the message back
+    /// if the channel closes or `deadline` passes first.
+    fn wait_taken_blocking(
+        &self,
+        ticket: u64,
+        deadline: Option<Instant>,
+    ) -> Result<(), SendTimeoutError<T>> {
+        let settled = listen::wait_blocking(&self.send_ops, deadline, || {
+            self.settled(ticket).then_some(())
+        });
+        match settled {
+            Some(()) => self.retract(ticket).map_err(SendTimeoutError::Disconnected),
+            None => self.retract(ticket).map_err(SendTimeoutError::Timeout),
+        }
+    }
+
+    /// Sends a message, waiting until there's room, the channel closes or
+    /// `deadline` passes.
+    pub(crate) async fn send_async(
+        &self,
+        msg: T,
+        deadline: Option<Instant>,
+    ) -> Result<(), SendTimeoutError<T>> {
+        let mut timer = deadline.map(time::delay_until);
+        let mut msg = Some(msg);
+        let offered = listen::wait_async(&self.send_ops, timer.as_mut(), || {
+            let t = match self.try_send(msg.take().unwrap()) {
+                Ok(()) => return Some(Ok(None)),
+                Err(TrySendError::Disconnected(t)) => {
+                    return Some(Err(SendTimeoutError::Disconnected(t)))
+                }
+                Err(TrySendError::Full(t)) => t,
+            };
+            match self.offer(t) {
+                Ok(ticket) => Some(Ok(Some(Offer { chan: self, ticket }))),
+                Err(t) => {
+                    msg = Some(t);
+                    None
+                }
+            }
+        })
+        .await;
+        let offer = match offered {
+            Some(Ok(Some(offer))) => offer,
+            Some(Ok(None)) => return Ok(()),
+            Some(Err(err)) => return Err(err),
+            None => return Err(SendTimeoutError::Timeout(msg.unwrap())),
+        };
+        // Without a buffer, wait for somebody to take it instead. If this
+        // future is dropped in the meantime, `offer` takes the message back.
+        let settled = listen::wait_async(&self.send_ops, timer.as_mut(), || {
+            self.settled(offer.ticket).then_some(())
+        })
+        .await;
+        match settled {
+            Some(()) => offer.retract().map_err(SendTimeoutError::Disconnected),
+            None => offer.retract().map_err(SendTimeoutError::Timeout),
+        }
+    }
+
+    /// Moves messages from the front of `batch` into the channel until either
+    /// runs out, all under one lock. Fails with `Full` if any are left over.
+    fn try_send_batch<E>(
+        &self,
+        batch: &mut VecDeque<T>,
+        reserve: impl FnOnce(&mut Queue<T>, usize) -> Result<(), E>,
+    ) -> Result<Result<(), TrySendError<()>>, E> {
+        let mut state = self.state();
+        if state.closed {
+            return Ok(Err(TrySendError::Disconnected(())));
+        }
+        let fits = state.room().min(batch.len());
+        reserve(&mut state.queue, fits)?;
+        for msg in batch.drain(..fits) {
+            state.queue.push(msg);
+        }
+        drop(state);
+        self.notify_receivers(fits);
+        match batch.is_empty() {
+            true => Ok(Ok(())),
+            false => Ok(Err(TrySendError::Full(()))),
+        }
+    }
+
+    /// Sends every message in `msgs`, moving as many as fit into the channel at
+    /// a time and blocking whenever it's full.
+    ///
+    /// If the channel closes, the first message which wasn't sent is returned
+    /// and the rest are dropped.
+    pub(crate) fn send_all_blocking(
+        &self,
+        msgs: impl IntoIterator<Item = T>,
+    ) -> Result<(), SendError<T>> {
+        let Ok(res) = self.send_all_blocking_with(
+            msgs,
+            |batch| {
+                batch.reserve(BATCH);
+                true
+            },
+            |batch| self.try_send_batch(batch, |_, _| Ok::<_, Infallible>(())),
+            |msg| Ok(self.send_blocking(msg, None)),
+        );
+        res
+    }
+
+    /// Like `send_all_blocking`, but fails with `SendAllocError` instead of
+    /// aborting if the queue can't grow to fit the messages. The error holds
+    /// the first message which wasn't sent, and the rest are dropped.
+    pub(crate) fn send_all_blocking_fallible(
+        &self,
+        msgs: impl IntoIterator<Item = T>,
+    ) -> Result<Result<(), SendError<T>>, SendAllocError<T>> {
+        self.send_all_blocking_with(
+            msgs,
+            |batch| batch.try_reserve(BATCH).is_ok(),
+            |batch| match self.try_send_batch(batch, Queue::try_reserve) {
+                Ok(res) => Ok(res),
+                // Nothing was moved, so the next message is still at the front.
+                Err(AllocError) => Err(SendAllocError(batch.pop_front().unwrap())),
+            },
+            |msg| self.send_blocking_fallible(msg, None),
+        )
+    }
+
+    /// `reserve` makes room for a batch, or returns `false` if there isn't any,
+    /// in which case each message is sent on its own instead.
+    fn send_all_blocking_with<E>(
+        &self,
+        msgs: impl IntoIterator<Item = T>,
+        reserve: impl FnOnce(&mut VecDeque<T>) -> bool,
+        try_send_batch: impl Fn(&mut VecDeque<T>) -> Result<Result<(), TrySendError<()>>, E>,
+        send: impl Fn(T) -> Result<Result<(), SendTimeoutError<T>>, E>,
+    ) -> Result<Result<(), SendError<T>>, E> {
+        let mut msgs = msgs.into_iter();
+        let mut batch = VecDeque::new();
+        if !reserve(&mut batch) {
+            for msg in msgs {
+                if let Err(err) = send(msg)? {
+                    return Ok(Err(SendError(err.into_inner())));
+                }
+            }
+            return Ok(Ok(()));
+        }
+        loop {
+            if batch.is_empty() {
+                batch.extend(msgs.by_ref().take(BATCH));
+                if batch.is_empty() {
+                    return Ok(Ok(()));
+                }
+            }
+            match try_send_batch(&mut batch)? {
+                Ok(()) => {}
+                Err(TrySendError::Disconnected(())) => {
+                    return Ok(Err(SendError(batch.pop_front().unwrap())))
+                }
+                // Wait for room by sending just the next message, then go back to batches.
+                Err(TrySendError::Full(())) => {
+                    let msg = batch.pop_front().unwrap();
+                    if let Err(err) = send(msg)? {
+                        return Ok(Err(SendError(err.into_inner())));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sends every message in `msgs`, moving as many as fit into the channel at
+    /// a time and waiting whenever it's full.
+    ///
+    /// If the channel closes, the first message which wasn't sent is returned
+    /// and the rest are dropped.
+    pub(crate) async fn send_all_async(
+        &self,
+        msgs: impl IntoIterator<Item = T>,
+    ) -> Result<(), SendError<T>> {
+        let mut msgs = msgs.into_iter();
+        let mut batch = VecDeque::with_capacity(BATCH);
+        loop {
+            if batch.is_empty() {
+                batch.extend(msgs.by_ref().take(BATCH));
+                if batch.is_empty() {
+                    return Ok(());
+                }
+            }
+            let Ok(res) = self.try_send_batch(&mut batch, |_, _| Ok::<_, Infallible>(()));
+            match res {
+                Ok(()) => {}
+                Err(TrySendError::Disconnected(())) => {
+                    return Err(SendError(batch.pop_front().unwrap()))
+                }
+                // Wait for room by sending just the next message, then go back to batches.
+                Err(TrySendError::Full(())) => {
+                    let msg = batch.pop_front().unwrap();
+                    let res = self.send_async(msg, None).await;
+                    res.map_err(|err| SendError(err.into_inner()))?;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.state();
+        match state.pop() {
+            Some(msg) => {
+                drop(state);
+                self.notify_senders(1);
+                Ok(msg)
+            }
+            None if state.closed => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Receives a message, blocking until one arrives, the channel closes and
+    /// is empty, or `deadline` passes.
+    pub(crate) fn recv_blocking(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        let mut waiting = None;
+        let received = listen::wait_blocking(&self.recv_ops, deadline, || {
+            let res = self.try_recv_ready();
+            if res.is_none() {
+                waiting.get_or_insert_with(|| Waiting::new(self));
+            }
+            res
+        });
+        // Counting as waiting means taking a message if one was handed over
+        // just now.
+        received.unwrap_or_else(|| {
+            let waiting = waiting.take().unwrap();
+            Waiting::give_up(waiting).ok_or(RecvTimeoutError::Timeout)
+        })
+    }
+
+    /// Receives a message, waiting until one arrives, the channel closes and is
+    /// empty, or `deadline` passes.
+    pub(crate) async fn recv_async(
+        &self,
+        deadline: Option<Instant>,
+    ) -> Result<T, RecvTimeoutError> {
+        let mut timer = deadline.map(time::delay_until);
+        let received = listen::wait_async(&self.recv_ops, timer.as_mut(), || self.try_recv_ready());
+        received.await.unwrap_or(Err(RecvTimeoutError::Timeout))
+    }
+
+    /// Receives a message, or returns `None` if there's none to receive yet.
+    fn try_recv_ready(&self) -> Option<Result<T, RecvTimeoutError>> {
+        match self.try_recv() {
+            Ok(msg) => Some(Ok(msg)),
+            Err(TryRecvError::Disconnected) => Some(Err(RecvTimeoutError::Disconnected)),
+            Err(TryRecvError::Empty) => None,
+        }
+    }
+
+    /// Closes the channel, returning `false` if it was already closed.
+    pub(crate) fn close(&self) -> bool {
+        let mut state = self.state();
+        if mem::replace(&mut state.closed, true) {
+            return false;
+        }
+        drop(state);
+        self.send_ops.notify(usize::MAX);
+        self.recv_ops.notify(usize::MAX);
+        true
+    }
+
+    /// Moves up to `max` messages which are already in the channel into `out`,
+    /// all under one lock, returning how many were moved.
+    pub(crate) fn try_recv_many(&self, out: &mut Vec<T>, max: usize) -> usize {
+        self.try_recv_many_with(out, max, Vec::reserve)
+    }
+
+    /// Like `try_recv_many`, but instead of aborting if `out` can't grow to fit
+    /// them all, only moves as many messages as it has room for.
+    pub(crate) fn try_recv_many_fallible(&self, out: &mut Vec<T>, max: usize) -> usize {
+        self.try_recv_many_with(out, max, |out, n| {
+            let _ = out.try_reserve(n);
+        })
+    }
+
+    fn try_recv_many_with(
+        &self,
+        out: &mut Vec<T>,
+        max: usize,
+        reserve: impl FnOnce(&mut Vec<T>, usize),
+    ) -> usize {
+        let mut state = self.state();
+        let mut n = state.queue.len().min(max);
+        reserve(out, n);
+        n = n.min(out.capacity() - out.len());
+        for _ in 0..n {
+            out.extend(state.pop());
+        }
+        drop(state);
+        self.notify_senders(n);
+        n
+    }
+
+    /// Returns whether `try_send` wouldn't fail with `Full` right now, either
+    /// because there's room or because the channel is closed. A rendezvous
+    /// channel never has room to wait for, so it always counts as having some.
+    #[cfg(feature = "futures")]
+    pub(crate) fn has_room(&self) -> bool {
+        let state = self.state();
+        self.rendezvous || state.closed || !state.is_full()
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.state().closed
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        // A rendezvous channel holds no messages, only ones in passing.
+        match self.rendezvous {
+            true => 0,
+            false => self.state().queue.len(),
+        }
+    }
+
+    pub(crate) fn dropped_count(&self) -> usize {
+        self.state().dropped
+    }
+
+    pub(crate) fn capacity(&self) -> Option<usize> {
+        self.state().cap
+    }
+
+    pub(crate) fn sender_count(&self) -> usize {
+        self.sender_count.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn receiver_count(&self) -> usize {
+        self.receiver_count.load(Ordering::SeqCst)
+    }
+}
+
+/// Counts a receiver as waiting for as long as it's alive.
+struct Waiting<'a, T>(&'a Channel<T>);
+
+impl<'a, T> Waiting<'a, T> {
+    fn new(chan: &'a Channel<T>) -> Self {
+        chan.state().waiting_receivers += 1;
+        Self(chan)
+    }
+
+    /// Stops waiting, unless a message was handed over in the meantime, which
+    /// is returned instead.
+    fn give_up(self) -> Option<T> {
+        let chan = self.0;
+        mem::forget(self);
+        let mut state = chan.state();
+        let msg = state.pop();
+        if msg.is_none() {
+            state.waiting_receivers -= 1;
+        }
+        drop(state);
+        chan.notify_senders(msg.is_some().into());
+        msg
+    }
+}
+
+impl<T> Drop for Waiting<'_, T> {
+    fn drop(&mut self) {
+        self.0.state().waiting_receivers -= 1;
+    }
+}
+
+/// A message offered on a rendezvous channel, which is taken back out of the
+/// channel if this is dropped before a receiver takes it.
+struct Offer<'a, T> {
+    chan: &'a Channel<T>,
+    ticket: u64,
+}
+
+impl<T> Offer<'_, T> {
+    /// Returns `Ok` if the message was taken, or takes it back.
+    fn retract(self) -> Result<(), T> {
+        let res = self.chan.retract(self.ticket);
+        mem::forget(self);
+        res
+    }
+}
+
+impl<T> Drop for Offer<'_, T> {
+    fn drop(&mut self) {
+        let _ = self.chan.retract(self.ticket);
+    }
+}
+
+/// A handle to the sending side of a [`Channel`].
+pub struct Sender<T> {
+    pub(crate) chan: Arc<Channel<T>>,
+    #[cfg(feature = "futures")]
+    pub(crate) sink: crate::sink::SinkState<T>,
+}
+
+// The buffered sink item is never pinned, so neither is the sender.
+impl<T> Unpin for Sender<T> {}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.sender_count.fetch_add(1, Ordering::SeqCst);
+        Self {
+            chan: self.chan.clone(),
+            #[cfg(feature = "futures")]
+            sink: Default::default(),
+        }
+    }
+}
+
+impl<T> Sender<T> {
+    pub(crate) fn downgrade(&self) -> WeakSender<T> {
+        WeakSender {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "futures")]
+        if let Some(ticket) = self.sink.offered.take() {
+            let _ = self.chan.retract(ticket);
+        }
+        if self.chan.sender_count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.chan.close();
+        }
+    }
+}
+
+/// A handle to a [`Channel`] which doesn't count as a sender.
+pub(crate) struct WeakSender<T> {
+    chan: Arc<Channel<T>>,
+}
+
+impl<T> WeakSender<T> {
+    /// Returns a new sender, unless the last one is already gone.
+    pub(crate) fn upgrade(&self) -> Option<Sender<T>> {
+        // Once the count hits zero the channel is closed for good, so never revive it.
+        self.chan
+            .sender_count
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n > 0).then_some(n + 1)
+            })
+            .ok()?;
+        Some(Sender {
+            chan: self.chan.clone(),
+            #[cfg(feature = "futures")]
+            sink: Default::default(),
+        })
+    }
+}
+
+impl<T> Clone for WeakSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+/// A handle to the receiving side of a [`Channel`].
+pub struct Receiver<T> {
+    pub(crate) chan: Arc<Channel<T>>,
+    /// Used by `poll_next`, which has nowhere else to keep it between polls.
+    listener: Option<EventListener>,
+}
+
+impl<T> Receiver<T> {
+    pub(crate) fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let chan = &self.chan;
+        listen::poll_wait(&mut self.listener, &chan.recv_ops, cx, || {
+            chan.try_recv_ready().map(Result::ok)
+        })
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.chan.receiver_count.fetch_add(1, Ordering::SeqCst);
+        Self {
+            chan: self.chan.clone(),
+            listener: None,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        if self.chan.receiver_count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.chan.close();
+        }
+    }
+}
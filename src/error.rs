@@ -0,0 +1,397 @@
+//! Error types shared by the sync and async channel halves.
+//!
+//! Conversions from the errors of `crossbeam-channel` and `async-channel` are
+//! available behind the features of the same names.
+
+use std::alloc::AllocError;
+use std::error;
+use std::fmt;
+
+/// An error returned from the `send` method.
+///
+/// The message could not be sent because the channel is disconnected.
+///
+/// The error contains the message so it can be recovered.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+impl<T> SendError<T> {
+    /// Unwraps the message.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "SendError(..)".fmt(f)
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "sending on a disconnected channel".fmt(f)
+    }
+}
+
+impl<T> error::Error for SendError<T> {}
+
+#[cfg(feature = "crossbeam-channel")]
+impl<T> From<crossbeam_channel::SendError<T>> for SendError<T> {
+    fn from(err: crossbeam_channel::SendError<T>) -> Self {
+        SendError(err.0)
+    }
+}
+
+#[cfg(feature = "async-channel")]
+impl<T> From<async_channel::SendError<T>> for SendError<T> {
+    fn from(err: async_channel::SendError<T>) -> Self {
+        SendError(err.0)
+    }
+}
+
+/// An error returned from the `try_send` method, and from
+/// [`OverflowSender::send`].
+///
+/// The error contains the message being sent so it can be recovered.
+///
+/// [`OverflowSender::send`]: crate::OverflowSender::send
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    /// The message could not be sent because the channel is full.
+    Full(T),
+    /// The message could not be sent because the channel is disconnected.
+    Disconnected(T),
+}
+
+impl<T> TrySendError<T> {
+    /// Unwraps the message.
+    pub fn into_inner(self) -> T {
+        match self {
+            TrySendError::Full(t) => t,
+            TrySendError::Disconnected(t) => t,
+        }
+    }
+
+    /// Returns `true` if the send operation failed because the channel is full.
+    pub fn is_full(&self) -> bool {
+        matches!(self, TrySendError::Full(_))
+    }
+
+    /// Returns `true` if the send operation failed because the channel is disconnected.
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, TrySendError::Disconnected(_))
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(..) => "Full(..)".fmt(f),
+            TrySendError::Disconnected(..) => "Disconnected(..)".fmt(f),
+        }
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full(..) => "sending on a full channel".fmt(f),
+            TrySendError::Disconnected(..) => "sending on a disconnected channel".fmt(f),
+        }
+    }
+}
+
+impl<T> error::Error for TrySendError<T> {}
+
+impl<T> From<SendError<T>> for TrySendError<T> {
+    fn from(err: SendError<T>) -> Self {
+        TrySendError::Disconnected(err.0)
+    }
+}
+
+#[cfg(feature = "crossbeam-channel")]
+impl<T> From<crossbeam_channel::TrySendError<T>> for TrySendError<T> {
+    fn from(err: crossbeam_channel::TrySendError<T>) -> Self {
+        match err {
+            crossbeam_channel::TrySendError::Full(t) => TrySendError::Full(t),
+            crossbeam_channel::TrySendError::Disconnected(t) => TrySendError::Disconnected(t),
+        }
+    }
+}
+
+#[cfg(feature = "async-channel")]
+impl<T> From<async_channel::TrySendError<T>> for TrySendError<T> {
+    fn from(err: async_channel::TrySendError<T>) -> Self {
+        match err {
+            async_channel::TrySendError::Full(t) => TrySendError::Full(t),
+            async_channel::TrySendError::Closed(t) => TrySendError::Disconnected(t),
+        }
+    }
+}
+
+/// An error returned from the `send_timeout` method.
+///
+/// The error contains the message being sent so it can be recovered.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum SendTimeoutError<T> {
+    /// The message could not be sent because the channel is full and the operation timed out.
+    Timeout(T),
+    /// The message could not be sent because the channel is disconnected.
+    Disconnected(T),
+}
+
+impl<T> SendTimeoutError<T> {
+    /// Unwraps the message.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendTimeoutError::Timeout(t) => t,
+            SendTimeoutError::Disconnected(t) => t,
+        }
+    }
+
+    /// Returns `true` if the send operation timed out.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, SendTimeoutError::Timeout(_))
+    }
+
+    /// Returns `true` if the send operation failed because the channel is disconnected.
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, SendTimeoutError::Disconnected(_))
+    }
+}
+
+impl<T> fmt::Debug for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(..) => "Timeout(..)".fmt(f),
+            SendTimeoutError::Disconnected(..) => "Disconnected(..)".fmt(f),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendTimeoutError::Timeout(..) => "timed out waiting on send operation".fmt(f),
+            SendTimeoutError::Disconnected(..) => "sending on a disconnected channel".fmt(f),
+        }
+    }
+}
+
+impl<T> error::Error for SendTimeoutError<T> {}
+
+impl<T> From<SendError<T>> for SendTimeoutError<T> {
+    fn from(err: SendError<T>) -> Self {
+        SendTimeoutError::Disconnected(err.0)
+    }
+}
+
+#[cfg(feature = "crossbeam-channel")]
+impl<T> From<crossbeam_channel::SendTimeoutError<T>> for SendTimeoutError<T> {
+    fn from(err: crossbeam_channel::SendTimeoutError<T>) -> Self {
+        match err {
+            crossbeam_channel::SendTimeoutError::Timeout(t) => SendTimeoutError::Timeout(t),
+            crossbeam_channel::SendTimeoutError::Disconnected(t) => {
+                SendTimeoutError::Disconnected(t)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async-channel")]
+impl<T> From<async_channel::SendError<T>> for SendTimeoutError<T> {
+    fn from(err: async_channel::SendError<T>) -> Self {
+        SendTimeoutError::Disconnected(err.0)
+    }
+}
+
+/// An error returned from sending under the `TRY` effect when the channel
+/// couldn't allocate room for the message.
+///
+/// The error contains the message so it can be recovered. It converts into an
+/// [`AllocError`], so `?` works in functions which only report that.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendAllocError<T>(pub T);
+
+impl<T> SendAllocError<T> {
+    /// Unwraps the message.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> fmt::Debug for SendAllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "SendAllocError(..)".fmt(f)
+    }
+}
+
+impl<T> fmt::Display for SendAllocError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "failed to allocate room for the message".fmt(f)
+    }
+}
+
+impl<T> error::Error for SendAllocError<T> {}
+
+impl<T> From<SendAllocError<T>> for AllocError {
+    fn from(_: SendAllocError<T>) -> Self {
+        AllocError
+    }
+}
+
+/// An error returned from the `recv` method.
+///
+/// A message could not be received because the channel is empty and disconnected.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "receiving on an empty and disconnected channel".fmt(f)
+    }
+}
+
+impl error::Error for RecvError {}
+
+#[cfg(feature = "crossbeam-channel")]
+impl From<crossbeam_channel::RecvError> for RecvError {
+    fn from(_: crossbeam_channel::RecvError) -> Self {
+        RecvError
+    }
+}
+
+#[cfg(feature = "async-channel")]
+impl From<async_channel::RecvError> for RecvError {
+    fn from(_: async_channel::RecvError) -> Self {
+        RecvError
+    }
+}
+
+/// An error returned from the `try_recv` method.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TryRecvError {
+    /// A message could not be received because the channel is empty.
+    Empty,
+    /// A message could not be received because the channel is empty and disconnected.
+    Disconnected,
+}
+
+impl TryRecvError {
+    /// Returns `true` if the receive operation failed because the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        matches!(self, TryRecvError::Empty)
+    }
+
+    /// Returns `true` if the receive operation failed because the channel is disconnected.
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, TryRecvError::Disconnected)
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => "receiving on an empty channel".fmt(f),
+            TryRecvError::Disconnected => "receiving on an empty and disconnected channel".fmt(f),
+        }
+    }
+}
+
+impl error::Error for TryRecvError {}
+
+impl From<RecvError> for TryRecvError {
+    fn from(_: RecvError) -> Self {
+        TryRecvError::Disconnected
+    }
+}
+
+#[cfg(feature = "crossbeam-channel")]
+impl From<crossbeam_channel::TryRecvError> for TryRecvError {
+    fn from(err: crossbeam_channel::TryRecvError) -> Self {
+        match err {
+            crossbeam_channel::TryRecvError::Empty => TryRecvError::Empty,
+            crossbeam_channel::TryRecvError::Disconnected => TryRecvError::Disconnected,
+        }
+    }
+}
+
+#[cfg(feature = "async-channel")]
+impl From<async_channel::TryRecvError> for TryRecvError {
+    fn from(err: async_channel::TryRecvError) -> Self {
+        match err {
+            async_channel::TryRecvError::Empty => TryRecvError::Empty,
+            async_channel::TryRecvError::Closed => TryRecvError::Disconnected,
+        }
+    }
+}
+
+/// An error returned from the `recv_timeout` method.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvTimeoutError {
+    /// A message could not be received because the channel is empty and the operation timed out.
+    Timeout,
+    /// The message could not be received because the channel is empty and disconnected.
+    Disconnected,
+}
+
+impl RecvTimeoutError {
+    /// Returns `true` if the receive operation timed out.
+    pub fn is_timeout(&self) -> bool {
+        matches!(self, RecvTimeoutError::Timeout)
+    }
+
+    /// Returns `true` if the receive operation failed because the channel is disconnected.
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, RecvTimeoutError::Disconnected)
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecvTimeoutError::Timeout => "timed out waiting on receive operation".fmt(f),
+            RecvTimeoutError::Disconnected => "channel is empty and disconnected".fmt(f),
+        }
+    }
+}
+
+impl error::Error for RecvTimeoutError {}
+
+impl From<RecvError> for RecvTimeoutError {
+    fn from(_: RecvError) -> Self {
+        RecvTimeoutError::Disconnected
+    }
+}
+
+#[cfg(feature = "crossbeam-channel")]
+impl From<crossbeam_channel::RecvTimeoutError> for RecvTimeoutError {
+    fn from(err: crossbeam_channel::RecvTimeoutError) -> Self {
+        match err {
+            crossbeam_channel::RecvTimeoutError::Timeout => RecvTimeoutError::Timeout,
+            crossbeam_channel::RecvTimeoutError::Disconnected => RecvTimeoutError::Disconnected,
+        }
+    }
+}
+
+#[cfg(feature = "async-channel")]
+impl From<async_channel::RecvError> for RecvTimeoutError {
+    fn from(_: async_channel::RecvError) -> Self {
+        RecvTimeoutError::Disconnected
+    }
+}
+
+/// An error returned from a oneshot receiver's `recv` method.
+///
+/// The sender was dropped without sending a message.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct Canceled;
+
+impl fmt::Display for Canceled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "oneshot sender dropped without sending".fmt(f)
+    }
+}
+
+impl error::Error for Canceled {}
@@ -20,151 +20,1021 @@
 //!     ?async fn recv(&self) -> Result<T, RecvError>;
 //! }
 //! ```
+//!
+//! # Effects
+//!
+//! Every type and function takes the effect it runs under as a const parameter.
+//! With [`Effects::NONE`] operations block, with [`Effects::ASYNC`] they return
+//! futures, and with [`Effects::TRY`] they block like sync ones, but sending
+//! reports failing to allocate room for the message instead of aborting.
+//! [`Effects::ALL`] isn't supported, so using it is a compile error:
+//!
+//! ```compile_fail,E0599
+//! use maybe_async_channel::{unbounded, Effects};
+//!
+//! let (sender, _receiver) = unbounded::<usize, { Effects::ALL }>();
+//! let _ = sender.send(1);
+//! ```
 
 #![forbid(unsafe_code, future_incompatible, rust_2018_idioms)]
 #![deny(nonstandard_style)]
 #![warn(missing_docs, unreachable_pub)]
 #![feature(type_alias_impl_trait)]
-#![feature(specialization)]
+#![feature(async_iterator)]
+#![feature(adt_const_params)]
+#![feature(allocator_api)]
 #![allow(incomplete_features)]
 
+use std::alloc::AllocError;
+use std::async_iter::AsyncIterator;
 use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
-use sender::SenderDataHelper;
+use maybe_async_proc_macro::maybe;
+use time::deadline_after;
 
-pub(crate) mod bounded {
-    use super::*;
+pub use broadcast::broadcast;
+pub use error::{
+    Canceled, RecvError, RecvTimeoutError, SendAllocError, SendError, SendTimeoutError,
+    TryRecvError, TrySendError,
+};
+pub use maybe_async_std::prelude::Effects;
+pub use oneshot::{oneshot, OneshotReceiver, OneshotSender};
+pub use watch::watch;
 
-    pub(crate) trait BoundedHelper<T, const ASYNC: bool> {
-        fn bounded(cap: usize) -> (Sender<T, ASYNC>, Receiver<T, ASYNC>);
-    }
+pub mod broadcast;
+pub mod select;
+pub mod watch;
 
-    impl<T> BoundedHelper<T, true> for () {
-        fn bounded(cap: usize) -> (Sender<T, true>, Receiver<T, true>) {
-            let (sender, receiver) = async_channel::bounded(cap);
-            let sender = Sender { sender };
-            let receiver = Receiver { receiver };
-            (sender, receiver)
-        }
+mod chan;
+mod error;
+mod listen;
+mod oneshot;
+
+mod time {
+    use std::future::{poll_fn, Future};
+    use std::pin::{pin, Pin};
+    use std::task::Poll;
+    use std::time::{Duration, Instant};
+
+    use futures_timer::Delay;
+
+    /// Converts a timeout into a deadline, saturating instead of overflowing.
+    pub(crate) fn deadline_after(timeout: Duration) -> Instant {
+        let now = Instant::now();
+        now.checked_add(timeout)
+            .unwrap_or_else(|| now + Duration::from_secs(86400 * 365 * 30))
     }
 
-    impl<T> BoundedHelper<T, false> for () {
-        fn bounded(cap: usize) -> (Sender<T, false>, Receiver<T, false>) {
-            let (sender, receiver) = crossbeam_channel::bounded(cap);
-            let sender = Sender { sender };
-            let receiver = Receiver { receiver };
-            (sender, receiver)
-        }
+    /// Creates a timer which fires at `deadline`.
+    pub(crate) fn delay_until(deadline: Instant) -> Delay {
+        Delay::new(deadline.saturating_duration_since(Instant::now()))
     }
 
-    // Actually only an impl for `MaybeAsync<false>`, as there are only two possible impls
-    // and we wrote both of them. Workaround for https://github.com/rust-lang/rust/pull/104803
-    impl<T, const ASYNC: bool> BoundedHelper<T, ASYNC> for () {
-        default fn bounded(_cap: usize) -> (Sender<T, ASYNC>, Receiver<T, ASYNC>) {
-            panic!("your trait solver is broken")
-        }
+    /// Polls `fut` until it completes or `timer` fires, whichever comes first.
+    pub(crate) async fn timeout<F: Future>(fut: F, timer: &mut Delay) -> Option<F::Output> {
+        let mut fut = pin!(fut);
+        poll_fn(|cx| {
+            if let Poll::Ready(t) = fut.as_mut().poll(cx) {
+                return Poll::Ready(Some(t));
+            }
+            Pin::new(&mut *timer).poll(cx).map(|()| None)
+        })
+        .await
     }
 }
 
 /// Creates a bounded channel.
 ///
-/// The created channel has space to hold at most `cap` messages at a time.
-pub fn bounded<T, const ASYNC: bool>(cap: usize) -> (Sender<T, ASYNC>, Receiver<T, ASYNC>) {
-    <() as bounded::BoundedHelper<T, ASYNC>>::bounded(cap)
+/// The created channel has space to hold at most `cap` messages at a time. If
+/// `cap` is zero, this creates a [`rendezvous`] channel.
+pub fn bounded<T, const EFFECT: Effects>(cap: usize) -> (Sender<T, EFFECT>, Receiver<T, EFFECT>) {
+    bounded_mixed(cap)
+}
+
+/// Creates a rendezvous channel, which has no space to hold messages at all.
+///
+/// Every message is handed straight from a sender to a receiver: `send` only
+/// returns once a receiver has taken the message, and takes it back if it times
+/// out or is cancelled first. `try_send` only succeeds if a receiver is already
+/// blocked in a sync `recv`, as that's sure to take the message. Likewise,
+/// `try_recv` only succeeds if a sender is waiting in `send`. This is the same
+/// as `bounded(0)`, in both modes.
+pub fn rendezvous<T, const EFFECT: Effects>() -> (Sender<T, EFFECT>, Receiver<T, EFFECT>) {
+    bounded_mixed(0)
 }
 
 /// Creates an unbounded channel.
 ///
 /// The created channel can hold an unlimited number of messages.
-pub fn unbounded<T, const ASYNC: bool>() -> (Sender<T, ASYNC>, Receiver<T, ASYNC>) {
-    todo!();
+pub fn unbounded<T, const EFFECT: Effects>() -> (Sender<T, EFFECT>, Receiver<T, EFFECT>) {
+    unbounded_mixed()
+}
+
+/// Creates a bounded channel which yields the greatest message first.
+///
+/// Apart from the order messages are received in, the channel behaves exactly
+/// like one created by [`bounded`].
+pub fn priority_bounded<T: Ord, const EFFECT: Effects>(
+    cap: usize,
+) -> (Sender<T, EFFECT>, Receiver<T, EFFECT>) {
+    let (sender, receiver) = chan::priority_channel(Some(cap));
+    (Sender { sender }, Receiver { receiver })
+}
+
+/// Creates a bounded channel which handles messages sent while it's full as
+/// `policy` says.
+///
+/// With [`OverflowPolicy::Block`] sending waits for room, like on a channel
+/// created by [`bounded`]. With any other policy, it never waits, in either
+/// mode.
+///
+/// # Panics
+///
+/// Panics if `cap` is zero and `policy` isn't [`OverflowPolicy::Block`]. A
+/// rendezvous channel is always full, so no message would ever get through.
+pub fn bounded_with_policy<T, const EFFECT: Effects>(
+    cap: usize,
+    policy: OverflowPolicy,
+) -> (OverflowSender<T, EFFECT>, Receiver<T, EFFECT>) {
+    assert!(
+        cap > 0 || policy == OverflowPolicy::Block,
+        "capacity cannot be zero unless the overflow policy is `Block`"
+    );
+    let (sender, receiver) = chan::channel_with_policy(cap, policy);
+    (OverflowSender { sender }, Receiver { receiver })
+}
+
+/// What a channel created by [`bounded_with_policy`] does with a message sent
+/// while it's full.
+///
+/// Messages dropped by a policy are returned from [`OverflowSender::send`]
+/// and [`OverflowSender::try_send`], and counted in
+/// [`OverflowSender::dropped_count`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum OverflowPolicy {
+    /// Wait for room, like any other bounded channel.
+    #[default]
+    Block,
+    /// Drop the oldest message in the channel to make room for the new one.
+    DropOldest,
+    /// Drop the message being sent, as if it had been sent and received.
+    DropNewest,
+    /// Fail right away: `send` returns the message in a [`TrySendError::Full`].
+    Error,
+}
+
+/// Creates a bounded channel whose halves may be in different modes.
+///
+/// Both halves share a single queue, so e.g. a blocking `send` on a worker
+/// thread wakes up an async task waiting in `recv`, and the other way round.
+///
+/// The created channel has space to hold at most `cap` messages at a time.
+pub fn bounded_mixed<T, const SEND_EFFECT: Effects, const RECV_EFFECT: Effects>(
+    cap: usize,
+) -> (Sender<T, SEND_EFFECT>, Receiver<T, RECV_EFFECT>) {
+    let (sender, receiver) = chan::channel(Some(cap));
+    (Sender { sender }, Receiver { receiver })
+}
+
+/// Creates an unbounded channel whose halves may be in different modes.
+///
+/// See [`bounded_mixed`] for how the two halves interact.
+///
+/// The created channel can hold an unlimited number of messages.
+pub fn unbounded_mixed<T, const SEND_EFFECT: Effects, const RECV_EFFECT: Effects>(
+) -> (Sender<T, SEND_EFFECT>, Receiver<T, RECV_EFFECT>) {
+    let (sender, receiver) = chan::channel(None);
+    (Sender { sender }, Receiver { receiver })
 }
 
 /// The sending side of a channel.
-pub struct Sender<T, const ASYNC: bool> {
-    sender: <() as sender::SenderDataHelper<T, ASYNC>>::Data,
+pub struct Sender<T, const EFFECT: Effects> {
+    sender: chan::Sender<T>,
 }
 
-impl<T, const ASYNC: bool> Sender<T, ASYNC> {
+impl<T, const EFFECT: Effects> Sender<T, EFFECT> {
     /// Send an item on the channel
-    pub fn send(&mut self, t: T) -> <() as sender::SenderDataHelper<T, ASYNC>>::Ret<'_> {
-        <() as SenderDataHelper<T, ASYNC>>::send(self, t)
+    ///
+    /// Under the `TRY` effect this blocks like a sync send, but gives `t` back
+    /// in a [`SendAllocError`] instead of aborting if the channel can't grow to
+    /// fit it. That error wraps the usual result rather than replacing it, as a
+    /// plain `Result<(), AllocError>` would leave no way to report a disconnect
+    /// or to hand the message back, and `?` turns it into an `AllocError`.
+    pub fn send<'a>(&'a self, t: T) -> <() as sender::send::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): sender::send::Helper<'a, T, EFFECT>,
+    {
+        sender::send::<T, EFFECT>(&self.sender, t)
+    }
+
+    /// Send every item of `msgs` on the channel, moving as many at a time as fit
+    ///
+    /// If the channel disconnects, or under `TRY` can't grow to fit them, the
+    /// first item which couldn't be sent is returned in the error, and the rest
+    /// are dropped.
+    pub fn send_all<'a, I: IntoIterator<Item = T>>(
+        &'a self,
+        msgs: I,
+    ) -> <() as sender::send_all::Helper<'a, T, I, EFFECT>>::Ret
+    where
+        (): sender::send_all::Helper<'a, T, I, EFFECT>,
+    {
+        sender::send_all::<T, I, EFFECT>(&self.sender, msgs)
+    }
+
+    /// Attempts to send an item on the channel without blocking.
+    ///
+    /// This is the same in both modes. Under `TRY`, it fails with a
+    /// [`SendAllocError`] instead of aborting, like `send`.
+    pub fn try_send<'a>(&'a self, t: T) -> <() as sender::try_send::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): sender::try_send::Helper<'a, T, EFFECT>,
+    {
+        <() as sender::try_send::Helper<'a, T, EFFECT>>::act(&self.sender, t)
+    }
+
+    /// Send an item on the channel, waiting at most `timeout` for space to free up
+    pub fn send_timeout<'a>(
+        &'a self,
+        t: T,
+        timeout: Duration,
+    ) -> <() as sender::send_deadline::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): sender::send_deadline::Helper<'a, T, EFFECT>,
+    {
+        sender::send_deadline::<T, EFFECT>(&self.sender, t, deadline_after(timeout))
+    }
+
+    /// Send an item on the channel, waiting until `deadline` for space to free up
+    pub fn send_deadline<'a>(
+        &'a self,
+        t: T,
+        deadline: Instant,
+    ) -> <() as sender::send_deadline::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): sender::send_deadline::Helper<'a, T, EFFECT>,
+    {
+        sender::send_deadline::<T, EFFECT>(&self.sender, t, deadline)
     }
 }
 
+/// A sending side of a channel which doesn't keep the channel open.
+///
+/// Weak senders don't count towards the number of senders, so the channel
+/// disconnects once the last [`Sender`] is dropped. Created by
+/// [`Sender::downgrade`].
+pub struct WeakSender<T, const EFFECT: Effects> {
+    sender: chan::WeakSender<T>,
+}
+
+/// The sending side of a channel created by [`bounded_with_policy`].
+///
+/// Its `send` and `try_send` follow the channel's [`OverflowPolicy`] and report
+/// what they did with the message, which the ones of a [`Sender`] couldn't.
+/// Otherwise it works like a [`Sender`], with a few operations left out:
+///
+/// - `send_all`, as it couldn't hand back every message the policy dropped.
+///   Calling `send` for each message reports them one at a time.
+/// - `send_timeout` and `send_deadline`, as only [`OverflowPolicy::Block`]
+///   ever waits. Use a channel created by [`bounded`] to wait for a while.
+/// - Sending in a [`Select`](select::Select), for the same reason.
+pub struct OverflowSender<T, const EFFECT: Effects> {
+    sender: chan::Sender<T>,
+}
+
+impl<T, const EFFECT: Effects> OverflowSender<T, EFFECT> {
+    /// Send an item on the channel, as its [`OverflowPolicy`] says
+    ///
+    /// Returns `Ok(None)` if the item was sent without dropping anything. If the
+    /// channel was full and the policy dropped a message to make room, that
+    /// message is returned in `Ok(Some(..))`: the oldest one in the channel
+    /// for [`OverflowPolicy::DropOldest`], and `t` itself for
+    /// [`OverflowPolicy::DropNewest`]. With [`OverflowPolicy::Error`], a full
+    /// channel fails with [`TrySendError::Full`] instead, and with
+    /// [`OverflowPolicy::Block`] this waits for room.
+    ///
+    /// Under `TRY`, this gives `t` back in a [`SendAllocError`] instead of
+    /// aborting if the channel can't grow to fit it, like [`Sender::send`].
+    pub fn send<'a>(&'a self, t: T) -> <() as sender::send_overflowing::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): sender::send_overflowing::Helper<'a, T, EFFECT>,
+    {
+        sender::send_overflowing::<T, EFFECT>(&self.sender, t)
+    }
+
+    /// Attempts to send an item on the channel without blocking, as its
+    /// [`OverflowPolicy`] says.
+    ///
+    /// This is the same as `send`, except that with [`OverflowPolicy::Block`]
+    /// a full channel fails with [`TrySendError::Full`] instead of waiting.
+    pub fn try_send<'a>(
+        &'a self,
+        t: T,
+    ) -> <() as sender::try_send_overflowing::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): sender::try_send_overflowing::Helper<'a, T, EFFECT>,
+    {
+        <() as sender::try_send_overflowing::Helper<'a, T, EFFECT>>::act(&self.sender, t)
+    }
+
+    /// Returns the number of messages the channel's [`OverflowPolicy`] has
+    /// dropped so far.
+    pub fn dropped_count(&self) -> usize {
+        self.sender.chan.dropped_count()
+    }
+}
+
+/// A sending side of a channel created by [`bounded_with_policy`] which doesn't
+/// keep the channel open.
+///
+/// Created by [`OverflowSender::downgrade`]. See [`WeakSender`].
+pub struct WeakOverflowSender<T, const EFFECT: Effects> {
+    sender: chan::WeakSender<T>,
+}
+
+/// Implements what [`Sender`] and [`OverflowSender`] have in common, given one
+/// of them and its weak counterpart. Both wrap a `chan::Sender`, and only
+/// differ in how they send.
+macro_rules! sender_common {
+    ($sender:ident, $weak:ident) => {
+        impl<T, const EFFECT: Effects> $sender<T, EFFECT> {
+            /// Closes the channel.
+            ///
+            /// Returns `true` if this call closed the channel, and `false` if it was
+            /// already closed. Messages already in the channel can still be received.
+            pub fn close(&self) -> bool {
+                self.sender.chan.close()
+            }
+
+            /// Returns the number of messages in the channel.
+            pub fn len(&self) -> usize {
+                self.sender.chan.len()
+            }
+
+            /// Returns the channel capacity if it's bounded.
+            pub fn capacity(&self) -> Option<usize> {
+                self.sender.chan.capacity()
+            }
+
+            /// Returns `true` if the channel is empty.
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Returns `true` if the channel is full.
+            pub fn is_full(&self) -> bool {
+                self.capacity() == Some(self.len())
+            }
+
+            /// Returns `true` if the channel is closed.
+            pub fn is_closed(&self) -> bool {
+                self.sender.chan.is_closed()
+            }
+
+            /// Returns the number of senders for the channel.
+            pub fn sender_count(&self) -> usize {
+                self.sender.chan.sender_count()
+            }
+
+            /// Returns the number of receivers for the channel.
+            pub fn receiver_count(&self) -> usize {
+                self.sender.chan.receiver_count()
+            }
+
+            #[doc = concat!("Creates a [`", stringify!($weak), "`] for the channel, which doesn't keep it open.")]
+            pub fn downgrade(&self) -> $weak<T, EFFECT> {
+                $weak {
+                    sender: self.sender.downgrade(),
+                }
+            }
+
+            /// Converts this sender into a blocking one, sending on the same channel.
+            pub fn into_blocking(self) -> $sender<T, { Effects::NONE }> {
+                $sender {
+                    sender: self.sender,
+                }
+            }
+
+            /// Converts this sender into an async one, sending on the same channel.
+            pub fn into_async(self) -> $sender<T, { Effects::ASYNC }> {
+                $sender {
+                    sender: self.sender,
+                }
+            }
+        }
+
+        impl<T, const EFFECT: Effects> Clone for $sender<T, EFFECT> {
+            fn clone(&self) -> Self {
+                $sender {
+                    sender: self.sender.clone(),
+                }
+            }
+        }
+
+        impl<T, const EFFECT: Effects> $weak<T, EFFECT> {
+            #[doc = concat!("Upgrades to a [`", stringify!($sender), "`], or returns `None` if every sender has been dropped.")]
+            pub fn upgrade(&self) -> Option<$sender<T, EFFECT>> {
+                let sender = self.sender.upgrade()?;
+                Some($sender { sender })
+            }
+        }
+
+        impl<T, const EFFECT: Effects> Clone for $weak<T, EFFECT> {
+            fn clone(&self) -> Self {
+                $weak {
+                    sender: self.sender.clone(),
+                }
+            }
+        }
+    };
+}
+
+sender_common!(Sender, WeakSender);
+sender_common!(OverflowSender, WeakOverflowSender);
+
 mod sender {
     use super::*;
-    /// Support trait for `Sender`.
-    pub trait SenderDataHelper<T, const ASYNC: bool> {
-        /// What is the type we're returning?
-        type Data;
-        /// What is the type `send` is returning
-        type Ret<'a>
-        where
-            Self: 'a,
-            T: 'a;
-        fn send(sender: &mut Sender<T, ASYNC>, _: T) -> Self::Ret<'_>;
+
+    #[maybe(async)]
+    pub(crate) fn send<'a, T: 'a>(sender: &'a chan::Sender<T>, msg: T) -> Result<(), SendError<T>> {
+        if ASYNC {
+            async move {
+                let res = sender.chan.send_async(msg, None).await;
+                res.map_err(|err| SendError(err.into_inner()))
+            }
+        } else {
+            sender
+                .chan
+                .send_blocking(msg, None)
+                .map_err(|err| SendError(err.into_inner()))
+        }
+    }
+
+    #[maybe(async)]
+    pub(crate) fn send_deadline<'a, T: 'a>(
+        sender: &'a chan::Sender<T>,
+        msg: T,
+        deadline: Instant,
+    ) -> Result<(), SendTimeoutError<T>> {
+        if ASYNC {
+            async move { sender.chan.send_async(msg, Some(deadline)).await }
+        } else {
+            sender.chan.send_blocking(msg, Some(deadline))
+        }
+    }
+
+    #[maybe(async)]
+    pub(crate) fn send_all<'a, T: 'a, I: IntoIterator<Item = T>>(
+        sender: &'a chan::Sender<T>,
+        msgs: I,
+    ) -> Result<(), SendError<T>> {
+        if ASYNC {
+            async move { sender.chan.send_all_async(msgs).await }
+        } else {
+            sender.chan.send_all_blocking(msgs)
+        }
+    }
+
+    // `maybe` handles a single effect per function, so the `TRY` variants are
+    // written out by hand. They block like the sync ones, but report failing to
+    // grow the queue instead of aborting.
+    impl<'a, T: 'a> send::Helper<'a, T, { Effects::TRY }> for () {
+        type Ret = Result<Result<(), SendError<T>>, SendAllocError<T>>;
+        fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret {
+            let res = sender.chan.send_blocking_fallible(msg, None)?;
+            Ok(res.map_err(|err| SendError(err.into_inner())))
+        }
+    }
+
+    impl<'a, T: 'a> send_deadline::Helper<'a, T, { Effects::TRY }> for () {
+        type Ret = Result<Result<(), SendTimeoutError<T>>, SendAllocError<T>>;
+        fn act(sender: &'a chan::Sender<T>, msg: T, deadline: Instant) -> Self::Ret {
+            sender.chan.send_blocking_fallible(msg, Some(deadline))
+        }
+    }
+
+    impl<'a, T: 'a, I: IntoIterator<Item = T>> send_all::Helper<'a, T, I, { Effects::TRY }> for () {
+        type Ret = Result<Result<(), SendError<T>>, SendAllocError<T>>;
+        fn act(sender: &'a chan::Sender<T>, msgs: I) -> Self::Ret {
+            sender.chan.send_all_blocking_fallible(msgs)
+        }
     }
 
-    impl<T> SenderDataHelper<T, true> for () {
-        type Data = async_channel::Sender<T>;
-        type Ret<'a> = impl std::future::Future<Output = Result<(), async_channel::SendError<T>>> + 'a where Self: 'a, T: 'a;
-        fn send(sender: &mut Sender<T, true>, msg: T) -> Self::Ret<'_> {
-            sender.sender.send(msg)
+    #[maybe(async)]
+    pub(crate) fn send_overflowing<'a, T: 'a>(
+        sender: &'a chan::Sender<T>,
+        msg: T,
+    ) -> Result<Option<T>, TrySendError<T>> {
+        if ASYNC {
+            async move { sender.chan.send_overflowing_async(msg).await }
+        } else {
+            sender.chan.send_overflowing_blocking(msg)
         }
     }
 
-    impl<T> SenderDataHelper<T, false> for () {
-        type Data = crossbeam_channel::Sender<T>;
-        type Ret<'a> = Result<(), crossbeam_channel::SendError<T>> where Self: 'a, T: 'a;
-        fn send(sender: &mut Sender<T, false>, msg: T) -> Self::Ret<'_> {
-            sender.sender.send(msg)
+    impl<'a, T: 'a> send_overflowing::Helper<'a, T, { Effects::TRY }> for () {
+        type Ret = Result<Result<Option<T>, TrySendError<T>>, SendAllocError<T>>;
+        fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret {
+            sender.chan.send_overflowing_fallible(msg)
         }
     }
 
-    // Actually only an impl for `MaybeAsync<false>`, as there are only two possible impls
-    // and we wrote both of them. Workaround for https://github.com/rust-lang/rust/pull/104803
-    impl<T, const B: bool> SenderDataHelper<T, B> for () {
-        default type Data = ();
-        default type Ret<'a> = () where Self: 'a, T: 'a;
-        default fn send(_sender: &mut Sender<T, B>, _msg: T) -> Self::Ret<'_> {
-            panic!("your trait solver is broken")
+    /// `try_send` never waits, so it's the same in both modes, and only
+    /// differs under `TRY`.
+    pub(crate) mod try_send {
+        use super::*;
+
+        /// Support trait for `Sender::try_send`.
+        pub trait Helper<'a, T, const EFFECT: Effects> {
+            /// What is the type `try_send` is returning
+            type Ret;
+            fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret;
+        }
+
+        impl<'a, T: 'a> Helper<'a, T, { Effects::NONE }> for () {
+            type Ret = Result<(), TrySendError<T>>;
+            fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret {
+                sender.chan.try_send(msg)
+            }
+        }
+
+        impl<'a, T: 'a> Helper<'a, T, { Effects::ASYNC }> for () {
+            type Ret = Result<(), TrySendError<T>>;
+            fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret {
+                sender.chan.try_send(msg)
+            }
+        }
+
+        impl<'a, T: 'a> Helper<'a, T, { Effects::TRY }> for () {
+            type Ret = Result<Result<(), TrySendError<T>>, SendAllocError<T>>;
+            fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret {
+                sender.chan.try_send_fallible(msg)
+            }
+        }
+    }
+
+    /// Like `try_send`, but reporting what the overflow policy dropped.
+    pub(crate) mod try_send_overflowing {
+        use super::*;
+
+        /// Support trait for `OverflowSender::try_send`.
+        pub trait Helper<'a, T, const EFFECT: Effects> {
+            /// What is the type `try_send_overflowing` is returning
+            type Ret;
+            fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret;
+        }
+
+        impl<'a, T: 'a> Helper<'a, T, { Effects::NONE }> for () {
+            type Ret = Result<Option<T>, TrySendError<T>>;
+            fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret {
+                sender.chan.try_send_overflowing(msg)
+            }
+        }
+
+        impl<'a, T: 'a> Helper<'a, T, { Effects::ASYNC }> for () {
+            type Ret = Result<Option<T>, TrySendError<T>>;
+            fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret {
+                sender.chan.try_send_overflowing(msg)
+            }
+        }
+
+        impl<'a, T: 'a> Helper<'a, T, { Effects::TRY }> for () {
+            type Ret = Result<Result<Option<T>, TrySendError<T>>, SendAllocError<T>>;
+            fn act(sender: &'a chan::Sender<T>, msg: T) -> Self::Ret {
+                sender.chan.try_send_overflowing_fallible(msg)
+            }
         }
     }
 }
 
 /// The Receiving side of a channel.
-pub struct Receiver<T, const ASYNC: bool> {
-    receiver: <Self as receiver::ReceiverDataHelper<ASYNC>>::Data,
+pub struct Receiver<T, const EFFECT: Effects> {
+    receiver: chan::Receiver<T>,
 }
 
-pub(crate) mod receiver {
+impl<T, const EFFECT: Effects> Receiver<T, EFFECT> {
+    /// Receive an item from the channel
+    pub fn recv<'a>(&'a self) -> <() as receiver::recv::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): receiver::recv::Helper<'a, T, EFFECT>,
+    {
+        receiver::recv::<T, EFFECT>(&self.receiver)
+    }
+
+    /// Receive an item from the channel, waiting at most `timeout` for one to arrive
+    pub fn recv_timeout<'a>(
+        &'a self,
+        timeout: Duration,
+    ) -> <() as receiver::recv_deadline::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): receiver::recv_deadline::Helper<'a, T, EFFECT>,
+    {
+        receiver::recv_deadline::<T, EFFECT>(&self.receiver, deadline_after(timeout))
+    }
+
+    /// Receive an item from the channel, waiting until `deadline` for one to arrive
+    pub fn recv_deadline<'a>(
+        &'a self,
+        deadline: Instant,
+    ) -> <() as receiver::recv_deadline::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): receiver::recv_deadline::Helper<'a, T, EFFECT>,
+    {
+        receiver::recv_deadline::<T, EFFECT>(&self.receiver, deadline)
+    }
+
+    /// Receive at least one and at most `max` items from the channel into `out`
+    ///
+    /// Waits for the first item to arrive, then takes whatever else is already
+    /// in the channel, returning how many items were received. Returns
+    /// `Ok(0)` right away if `max` is zero.
+    ///
+    /// Under `TRY`, this fails with an `AllocError` instead of aborting if `out`
+    /// can't grow to fit the first item, and otherwise takes fewer items if it
+    /// can't grow to fit them all.
+    pub fn recv_many<'a>(
+        &'a self,
+        out: &'a mut Vec<T>,
+        max: usize,
+    ) -> <() as receiver::recv_many::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): receiver::recv_many::Helper<'a, T, EFFECT>,
+    {
+        receiver::recv_many::<T, EFFECT>(&self.receiver, out, max)
+    }
+
+    /// Attempts to receive an item from the channel without blocking.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.receiver.chan.try_recv()
+    }
+
+    /// Closes the channel.
+    ///
+    /// Returns `true` if this call closed the channel, and `false` if it was
+    /// already closed. Messages already in the channel can still be received.
+    pub fn close(&self) -> bool {
+        self.receiver.chan.close()
+    }
+
+    /// Returns the number of messages in the channel.
+    pub fn len(&self) -> usize {
+        self.receiver.chan.len()
+    }
+
+    /// Returns the channel capacity if it's bounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.receiver.chan.capacity()
+    }
+
+    /// Returns `true` if the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the channel is full.
+    pub fn is_full(&self) -> bool {
+        self.capacity() == Some(self.len())
+    }
+
+    /// Returns `true` if the channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.receiver.chan.is_closed()
+    }
+
+    /// Returns the number of senders for the channel.
+    pub fn sender_count(&self) -> usize {
+        self.receiver.chan.sender_count()
+    }
+
+    /// Returns the number of receivers for the channel.
+    pub fn receiver_count(&self) -> usize {
+        self.receiver.chan.receiver_count()
+    }
+
+    /// Converts this receiver into a blocking one, receiving from the same channel.
+    pub fn into_blocking(self) -> Receiver<T, { Effects::NONE }> {
+        Receiver {
+            receiver: self.receiver,
+        }
+    }
+
+    /// Converts this receiver into an async one, receiving from the same channel.
+    pub fn into_async(self) -> Receiver<T, { Effects::ASYNC }> {
+        Receiver {
+            receiver: self.receiver,
+        }
+    }
+}
+
+impl<T, const EFFECT: Effects> Clone for Receiver<T, EFFECT> {
+    fn clone(&self) -> Self {
+        Receiver {
+            receiver: self.receiver.clone(),
+        }
+    }
+}
+
+mod receiver {
     use super::*;
 
-    /// Support trait for `Sender`.
-    pub(crate) trait ReceiverDataHelper<const ASYNC: bool> {
-        /// What is the type we're returning?
-        type Data;
+    #[maybe(async)]
+    pub(crate) fn recv<'a, T: 'a>(receiver: &'a chan::Receiver<T>) -> Result<T, RecvError> {
+        if ASYNC {
+            async move {
+                let res = receiver.chan.recv_async(None).await;
+                res.map_err(|_| RecvError)
+            }
+        } else {
+            receiver.chan.recv_blocking(None).map_err(|_| RecvError)
+        }
+    }
+
+    #[maybe(async)]
+    pub(crate) fn recv_deadline<'a, T: 'a>(
+        receiver: &'a chan::Receiver<T>,
+        deadline: Instant,
+    ) -> Result<T, RecvTimeoutError> {
+        if ASYNC {
+            async move { receiver.chan.recv_async(Some(deadline)).await }
+        } else {
+            receiver.chan.recv_blocking(Some(deadline))
+        }
+    }
+
+    #[maybe(async)]
+    pub(crate) fn recv_many<'a, T: 'a>(
+        receiver: &'a chan::Receiver<T>,
+        out: &'a mut Vec<T>,
+        max: usize,
+    ) -> Result<usize, RecvError> {
+        if ASYNC {
+            async move {
+                if max == 0 {
+                    return Ok(0);
+                }
+                let msg = receiver.chan.recv_async(None).await;
+                out.push(msg.map_err(|_| RecvError)?);
+                Ok(1 + receiver.chan.try_recv_many(out, max - 1))
+            }
+        } else {
+            if max == 0 {
+                return Ok(0);
+            }
+            let msg = receiver.chan.recv_blocking(None);
+            out.push(msg.map_err(|_| RecvError)?);
+            Ok(1 + receiver.chan.try_recv_many(out, max - 1))
+        }
+    }
+
+    // Under `TRY`, operations which never allocate, like receiving a single
+    // message, are the same as in sync mode. Their `TRY` impls here and in the
+    // other channel modules just forward to the sync ones.
+    impl<'a, T: 'a> recv::Helper<'a, T, { Effects::TRY }> for () {
+        type Ret = Result<T, RecvError>;
+        fn act(receiver: &'a chan::Receiver<T>) -> Self::Ret {
+            recv::<T, { Effects::NONE }>(receiver)
+        }
     }
 
-    impl<T> ReceiverDataHelper<true> for Receiver<T, true> {
-        type Data = async_channel::Receiver<T>;
+    impl<'a, T: 'a> recv_deadline::Helper<'a, T, { Effects::TRY }> for () {
+        type Ret = Result<T, RecvTimeoutError>;
+        fn act(receiver: &'a chan::Receiver<T>, deadline: Instant) -> Self::Ret {
+            recv_deadline::<T, { Effects::NONE }>(receiver, deadline)
+        }
+    }
+
+    impl<'a, T: 'a> recv_many::Helper<'a, T, { Effects::TRY }> for () {
+        type Ret = Result<Result<usize, RecvError>, AllocError>;
+        fn act(receiver: &'a chan::Receiver<T>, out: &'a mut Vec<T>, max: usize) -> Self::Ret {
+            if max == 0 {
+                return Ok(Ok(0));
+            }
+            // Make room before taking the first message, so it can't get lost.
+            out.try_reserve(1).map_err(|_| AllocError)?;
+            match receiver.chan.recv_blocking(None) {
+                Ok(msg) => out.push(msg),
+                Err(_) => return Ok(Err(RecvError)),
+            }
+            Ok(Ok(1 + receiver.chan.try_recv_many_fallible(out, max - 1)))
+        }
+    }
+}
+
+impl<T> Receiver<T, { Effects::NONE }> {
+    /// A blocking iterator over messages in the channel.
+    ///
+    /// Each call to `next` blocks waiting for the next message, and the iterator
+    /// ends once the channel is disconnected.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { receiver: self }
+    }
+
+    /// A non-blocking iterator over messages in the channel.
+    ///
+    /// Each call to `next` returns a message if there is one ready, and the
+    /// iterator ends as soon as the channel is empty.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { receiver: self }
+    }
+}
+
+impl<T> std::iter::Iterator for Receiver<T, { Effects::NONE }> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.recv().ok()
     }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T, { Effects::NONE }> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
 
-    impl<T> ReceiverDataHelper<false> for Receiver<T, false> {
-        type Data = crossbeam_channel::Receiver<T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
+}
 
-    // Actually only an impl for `MaybeAsync<false>`, as there are only two possible impls
-    // and we wrote both of them. Workaround for https://github.com/rust-lang/rust/pull/104803
-    impl<T, const ASYNC: bool> ReceiverDataHelper<ASYNC> for Receiver<T, ASYNC> {
-        default type Data = ();
+/// A blocking iterator over messages in a channel.
+///
+/// Created by [`Receiver::iter`].
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct Iter<'a, T> {
+    receiver: &'a Receiver<T, { Effects::NONE }>,
+}
+
+impl<T> std::iter::Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.recv().ok()
+    }
+}
+
+/// A non-blocking iterator over messages in a channel.
+///
+/// Created by [`Receiver::try_iter`].
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct TryIter<'a, T> {
+    receiver: &'a Receiver<T, { Effects::NONE }>,
+}
+
+impl<T> std::iter::Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl<T> AsyncIterator for Receiver<T, { Effects::ASYNC }> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.receiver.poll_next(cx)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<T> futures_core::Stream for Receiver<T, { Effects::ASYNC }> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.receiver.poll_next(cx)
+    }
+}
+
+#[cfg(feature = "futures")]
+mod sink {
+    use super::*;
+
+    use event_listener::EventListener;
+    use futures_sink::Sink;
+
+    /// A message `start_send` couldn't fit into the channel yet.
+    pub(crate) struct SinkState<T> {
+        pending: Option<T>,
+        /// The ticket of a message offered on a rendezvous channel, which
+        /// hasn't been taken yet.
+        pub(crate) offered: Option<u64>,
+        listener: Option<EventListener>,
+    }
+
+    impl<T> Default for SinkState<T> {
+        fn default() -> Self {
+            Self {
+                pending: None,
+                offered: None,
+                listener: None,
+            }
+        }
+    }
+
+    impl<T> chan::Sender<T> {
+        /// Moves the pending message into the channel, waiting for a receive if it's full.
+        ///
+        /// On a rendezvous channel the message is offered instead, and this
+        /// waits for a receiver to take it.
+        fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TrySendError<T>>> {
+            let chan = &self.chan;
+            let SinkState {
+                pending,
+                offered,
+                listener,
+            } = &mut self.sink;
+            listen::poll_wait(listener, &chan.send_ops, cx, || {
+                if let Some(ticket) = *offered {
+                    if !chan.settled(ticket) {
+                        return None;
+                    }
+                    *offered = None;
+                    return Some(chan.retract(ticket).map_err(TrySendError::Disconnected));
+                }
+                let Some(msg) = pending.take() else {
+                    return Some(Ok(()));
+                };
+                match chan.try_send(msg) {
+                    Ok(()) => Some(Ok(())),
+                    Err(TrySendError::Disconnected(msg)) => {
+                        Some(Err(TrySendError::Disconnected(msg)))
+                    }
+                    Err(TrySendError::Full(msg)) => {
+                        match chan.offer(msg) {
+                            Ok(ticket) => *offered = Some(ticket),
+                            Err(msg) => *pending = Some(msg),
+                        }
+                        None
+                    }
+                }
+            })
+        }
+
+        /// Waits until the channel has room for a message, or is closed so
+        /// sending fails right away.
+        fn poll_room(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+            let chan = &self.chan;
+            listen::poll_wait(&mut self.sink.listener, &chan.send_ops, cx, || {
+                chan.has_room().then_some(())
+            })
+        }
+    }
+
+    /// `poll_ready` waits until the pending message is sent and the channel has
+    /// room for another, so `start_send` hardly ever holds on to a message. It
+    /// still does if another sender takes the room in between, or on a
+    /// rendezvous channel, where it's offered once flushed.
+    ///
+    /// Calling `start_send` while a message is still pending, without waiting
+    /// for `poll_ready` first, fails with [`TrySendError::Full`].
+    impl<T> Sink<T> for Sender<T, { Effects::ASYNC }> {
+        type Error = TrySendError<T>;
+
+        fn poll_ready(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            futures_core::ready!(self.sender.poll_pending(cx))?;
+            self.sender.poll_room(cx).map(Ok)
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, msg: T) -> Result<(), Self::Error> {
+            let sink = &self.sender.sink;
+            if sink.pending.is_some() || sink.offered.is_some() {
+                return Err(TrySendError::Full(msg));
+            }
+            match self.sender.chan.try_send(msg) {
+                Err(TrySendError::Full(msg)) => {
+                    self.sender.sink.pending = Some(msg);
+                    Ok(())
+                }
+                res => res,
+            }
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.sender.poll_pending(cx)
+        }
+
+        fn poll_close(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            futures_core::ready!(self.sender.poll_pending(cx))?;
+            self.sender.chan.close();
+            Poll::Ready(Ok(()))
+        }
     }
 }
 
 /// An interface for dealing with iterators.
 #[must_use = "iterators are lazy and do nothing unless consumed"]
-pub trait Iterator<const ASYNC: bool> {
+pub trait Iterator<const EFFECT: Effects> {
     type Item;
     type MaybeFuture<'a>
     where
@@ -172,34 +1042,62 @@ pub trait Iterator<const ASYNC: bool> {
     fn next<'a>(&'a mut self) -> Self::MaybeFuture<'a>;
 }
 
-// Actually only an impl for `MaybeAsync<false>`, as there are only two possible impls
-// and we wrote both of them. Workaround for https://github.com/rust-lang/rust/pull/104803
-impl<T, const ASYNC: bool> Iterator<ASYNC> for Receiver<T, ASYNC> {
-    default type Item = ();
-    default type MaybeFuture<'a> = ()
+impl<T> Iterator<{ Effects::NONE }> for Receiver<T, { Effects::NONE }> {
+    type Item = T;
+    type MaybeFuture<'a> = Option<T>
     where
         Self: 'a;
-    default fn next<'a>(&'a mut self) -> Self::MaybeFuture<'a> {
-        panic!("your trait solver is broken")
+    fn next<'a>(&'a mut self) -> Self::MaybeFuture<'a> {
+        self.recv().ok()
     }
 }
 
-impl<T> Iterator<false> for Receiver<T, false> {
+impl<T> Iterator<{ Effects::TRY }> for Receiver<T, { Effects::TRY }> {
     type Item = T;
     type MaybeFuture<'a> = Option<T>
     where
         Self: 'a;
     fn next<'a>(&'a mut self) -> Self::MaybeFuture<'a> {
-        self.receiver.recv().ok()
+        self.recv().ok()
+    }
+}
+
+impl<T> Iterator<{ Effects::ASYNC }> for Receiver<T, { Effects::ASYNC }> {
+    type Item = T;
+    type MaybeFuture<'a> = impl Future<Output = Option<T>> + 'a
+    where
+        Self: 'a;
+    fn next<'a>(&'a mut self) -> Self::MaybeFuture<'a> {
+        async move { self.recv().await.ok() }
+    }
+}
+
+impl<T> Iterator<{ Effects::NONE }> for &Receiver<T, { Effects::NONE }> {
+    type Item = T;
+    type MaybeFuture<'a> = Option<T>
+    where
+        Self: 'a;
+    fn next<'a>(&'a mut self) -> Self::MaybeFuture<'a> {
+        self.recv().ok()
+    }
+}
+
+impl<T> Iterator<{ Effects::TRY }> for &Receiver<T, { Effects::TRY }> {
+    type Item = T;
+    type MaybeFuture<'a> = Option<T>
+    where
+        Self: 'a;
+    fn next<'a>(&'a mut self) -> Self::MaybeFuture<'a> {
+        self.recv().ok()
     }
 }
 
-impl<T> Iterator<true> for Receiver<T, true> {
+impl<T> Iterator<{ Effects::ASYNC }> for &Receiver<T, { Effects::ASYNC }> {
     type Item = T;
     type MaybeFuture<'a> = impl Future<Output = Option<T>> + 'a
     where
         Self: 'a;
     fn next<'a>(&'a mut self) -> Self::MaybeFuture<'a> {
-        async move { self.receiver.recv().await.ok() }
+        async move { self.recv().await.ok() }
     }
 }
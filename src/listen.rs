@@ -0,0 +1,83 @@
+//! Waiting for an operation to succeed, on any kind of channel.
+//!
+//! Every wait works the same way: attempt the operation, and if it can't
+//! complete yet, start listening for the event which could change that, then
+//! attempt it again before actually waiting. Listening before the second
+//! attempt means a notification sent in between is never missed, and waiting
+//! only after it means we never sleep through a change we could have seen.
+
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use event_listener::{Event, EventListener};
+use futures_timer::Delay;
+
+use crate::time;
+
+/// Polls `attempt` until it returns `Some`, using `listener` to register for a
+/// wakeup from `event` through `cx` between attempts.
+///
+/// `listener` is kept by the caller, so the wait can be picked up across polls.
+pub(crate) fn poll_wait<R>(
+    listener: &mut Option<EventListener>,
+    event: &Event,
+    cx: &mut Context<'_>,
+    mut attempt: impl FnMut() -> Option<R>,
+) -> Poll<R> {
+    loop {
+        if let Some(r) = attempt() {
+            *listener = None;
+            return Poll::Ready(r);
+        }
+        match listener.as_mut() {
+            Some(l) => {
+                futures_core::ready!(Pin::new(l).poll(cx));
+                *listener = None;
+            }
+            None => *listener = Some(event.listen()),
+        }
+    }
+}
+
+/// Blocks until `attempt` returns `Some`, waking up to retry whenever `event`
+/// is notified. Returns `None` if `deadline` passes first, so never without one.
+pub(crate) fn wait_blocking<R>(
+    event: &Event,
+    deadline: Option<Instant>,
+    mut attempt: impl FnMut() -> Option<R>,
+) -> Option<R> {
+    let mut listener = None;
+    loop {
+        if let Some(r) = attempt() {
+            return Some(r);
+        }
+        match listener.take() {
+            None => listener = Some(event.listen()),
+            Some(listener) => match deadline {
+                Some(deadline) => {
+                    if !listener.wait_deadline(deadline) {
+                        return None;
+                    }
+                }
+                None => listener.wait(),
+            },
+        }
+    }
+}
+
+/// Waits until `attempt` returns `Some`, retrying whenever `event` is
+/// notified. Returns `None` if `timer` fires first, so never without one.
+pub(crate) async fn wait_async<R>(
+    event: &Event,
+    timer: Option<&mut Delay>,
+    mut attempt: impl FnMut() -> Option<R>,
+) -> Option<R> {
+    let mut listener = None;
+    let ready = poll_fn(|cx| poll_wait(&mut listener, event, cx, &mut attempt));
+    match timer {
+        Some(timer) => time::timeout(ready, timer).await,
+        None => Some(ready.await),
+    }
+}
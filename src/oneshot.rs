@@ -0,0 +1,80 @@
+//! A channel for sending a single message.
+
+use maybe_async_proc_macro::maybe;
+
+use crate::{chan, Canceled, Effects, SendError};
+
+/// Creates a channel for sending a single message.
+///
+/// This is the usual way to send a response back to whoever made a request.
+pub fn oneshot<T, const EFFECT: Effects>() -> (OneshotSender<T>, OneshotReceiver<T, EFFECT>) {
+    let (sender, receiver) = chan::channel(Some(1));
+    (OneshotSender { sender }, OneshotReceiver { receiver })
+}
+
+/// The sending side of a oneshot channel.
+///
+/// Sending never blocks, so the same sender works with both kinds of receiver.
+pub struct OneshotSender<T> {
+    sender: chan::Sender<T>,
+}
+
+impl<T> OneshotSender<T> {
+    /// Send the message, consuming the sender.
+    ///
+    /// Fails if the receiver has already been dropped. This never allocates, as
+    /// room for the message is made when the channel is created.
+    pub fn send(self, t: T) -> Result<(), SendError<T>> {
+        self.sender
+            .chan
+            .try_send(t)
+            .map_err(|err| SendError(err.into_inner()))
+    }
+}
+
+/// The receiving side of a oneshot channel.
+pub struct OneshotReceiver<T, const EFFECT: Effects> {
+    receiver: chan::Receiver<T>,
+}
+
+impl<T, const EFFECT: Effects> OneshotReceiver<T, EFFECT> {
+    /// Receive the message, consuming the receiver.
+    ///
+    /// Fails with [`Canceled`] if the sender was dropped without sending.
+    pub fn recv(self) -> <() as receiver::recv::Helper<T, EFFECT>>::Ret
+    where
+        (): receiver::recv::Helper<T, EFFECT>,
+    {
+        receiver::recv::<T, EFFECT>(self)
+    }
+}
+
+mod receiver {
+    use super::*;
+
+    #[maybe(async)]
+    pub(crate) fn recv<T>(receiver: OneshotReceiver<T, EFFECT>) -> Result<T, Canceled> {
+        if ASYNC {
+            async move {
+                let res = receiver.receiver.chan.recv_async(None).await;
+                res.map_err(|_| Canceled)
+            }
+        } else {
+            receiver
+                .receiver
+                .chan
+                .recv_blocking(None)
+                .map_err(|_| Canceled)
+        }
+    }
+
+    impl<T> recv::Helper<T, { Effects::TRY }> for () {
+        type Ret = Result<T, Canceled>;
+        fn act(receiver: OneshotReceiver<T, { Effects::TRY }>) -> Self::Ret {
+            let receiver = OneshotReceiver {
+                receiver: receiver.receiver,
+            };
+            recv::<T, { Effects::NONE }>(receiver)
+        }
+    }
+}
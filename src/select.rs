@@ -0,0 +1,340 @@
+//! Waiting on several channel operations at once.
+//!
+//! A [`Select`] is built up from operations on `Sender<T, EFFECT>`s and
+//! `Receiver<T, EFFECT>`s, each with a closure to run when it completes. Waiting
+//! on it blocks until the first operation completes in sync mode, and returns a
+//! future which does the same in async mode:
+//!
+//! ```
+//! use maybe_async_channel::select::Select;
+//! use maybe_async_channel::{unbounded, Effects};
+//!
+//! let (sender1, receiver1) = unbounded::<usize, { Effects::NONE }>();
+//! let (_sender2, receiver2) = unbounded::<&str, { Effects::NONE }>();
+//! sender1.send(1).unwrap();
+//!
+//! let received = Select::<_, { Effects::NONE }>::new()
+//!     .recv(&receiver1, |res| res.unwrap().to_string())
+//!     .recv(&receiver2, |res| res.unwrap().to_string())
+//!     .wait();
+//! assert_eq!(received, "1");
+//! ```
+
+use std::alloc::AllocError;
+use std::future::{poll_fn, Future};
+use std::pin::{pin, Pin};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+
+use event_listener::EventListener;
+use futures_timer::Delay;
+use maybe_async_proc_macro::maybe;
+
+use crate::chan::Channel;
+use crate::listen;
+use crate::time::{deadline_after, delay_until};
+use crate::{Effects, Receiver, RecvError, SendError, Sender, TryRecvError, TrySendError};
+
+/// A set of channel operations to wait on, of which exactly one completes.
+///
+/// If several operations are ready at the same time, the one added first wins.
+pub struct Select<'a, R, const EFFECT: Effects> {
+    ops: Vec<Box<dyn Operation<R> + 'a>>,
+    default: Option<Box<dyn FnOnce() -> R + 'a>>,
+    timeout: Option<(Delay, Box<dyn FnOnce() -> R + 'a>)>,
+}
+
+impl<'a, R, const EFFECT: Effects> Select<'a, R, EFFECT> {
+    /// Creates an empty set of operations.
+    pub fn new() -> Self {
+        Self {
+            ops: Vec::new(),
+            default: None,
+            timeout: None,
+        }
+    }
+
+    /// Adds a receive on `receiver`, passing the result to `f`.
+    pub fn recv<T: 'a>(
+        mut self,
+        receiver: &'a Receiver<T, EFFECT>,
+        f: impl FnOnce(Result<T, RecvError>) -> R + 'a,
+    ) -> Self {
+        self.ops.push(Box::new(RecvOp {
+            chan: &receiver.receiver.chan,
+            f: Some(f),
+            listener: None,
+        }));
+        self
+    }
+
+    /// Adds a send of `msg` on `sender`, passing the result to `f`.
+    ///
+    /// If another operation completes first, `msg` is dropped. On a rendezvous
+    /// channel, the message is offered to receivers while waiting, and taken
+    /// back before any other operation completes. With a `default`, nothing
+    /// waits, so it's only sent if a receiver is already blocked in a sync `recv`.
+    pub fn send<T: 'a>(
+        mut self,
+        sender: &'a Sender<T, EFFECT>,
+        msg: T,
+        f: impl FnOnce(Result<(), SendError<T>>) -> R + 'a,
+    ) -> Self {
+        self.ops.push(Box::new(SendOp {
+            chan: &sender.sender.chan,
+            msg: Some(msg),
+            offered: None,
+            f: Some(f),
+            listener: None,
+            fallible: EFFECT == Effects::TRY,
+        }));
+        self
+    }
+
+    /// Runs `f` instead of waiting if no operation is ready right away.
+    pub fn default(mut self, f: impl FnOnce() -> R + 'a) -> Self {
+        self.default = Some(Box::new(f));
+        self
+    }
+
+    /// Runs `f` if no operation completes within `timeout`.
+    pub fn timeout(self, timeout: Duration, f: impl FnOnce() -> R + 'a) -> Self {
+        self.deadline(deadline_after(timeout), f)
+    }
+
+    /// Runs `f` if no operation completes before `deadline`.
+    pub fn deadline(mut self, deadline: Instant, f: impl FnOnce() -> R + 'a) -> Self {
+        self.timeout = Some((delay_until(deadline), Box::new(f)));
+        self
+    }
+
+    /// Waits for the first operation to complete, returning what its closure returned.
+    ///
+    /// Under `TRY`, this fails with an `AllocError` instead of aborting if a send
+    /// can't make room for its message, which is then dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no operations and neither a default nor a timeout,
+    /// as nothing could ever complete.
+    pub fn wait(self) -> <() as wait::wait::Helper<'a, R, EFFECT>>::Ret
+    where
+        R: 'a,
+        (): wait::wait::Helper<'a, R, EFFECT>,
+    {
+        assert!(
+            !self.ops.is_empty() || self.default.is_some() || self.timeout.is_some(),
+            "no operations have been added to `Select`"
+        );
+        wait::wait::<R, EFFECT>(self)
+    }
+
+    async fn run_async(mut self) -> Result<R, AllocError> {
+        poll_fn(|cx| self.poll(cx)).await
+    }
+
+    fn run_blocking(mut self) -> Result<R, AllocError> {
+        block_on(poll_fn(|cx| self.poll(cx)))
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<R, AllocError>> {
+        // Take back whatever was offered while waiting, so that nothing but the
+        // operation which completes can happen from here on. An offer which was
+        // taken already is that operation.
+        for op in &mut self.ops {
+            if let Some(r) = op.retract() {
+                return Poll::Ready(r);
+            }
+        }
+        for op in &mut self.ops {
+            if let Poll::Ready(r) = op.poll(cx) {
+                return Poll::Ready(r);
+            }
+        }
+        if let Some(f) = self.default.take() {
+            return Poll::Ready(Ok(f()));
+        }
+        if let Some((timer, _)) = &mut self.timeout {
+            if Pin::new(timer).poll(cx).is_ready() {
+                let (_, f) = self.timeout.take().unwrap();
+                return Poll::Ready(Ok(f()));
+            }
+        }
+        // Only offer once nothing else can complete in this poll.
+        for op in &mut self.ops {
+            op.offer();
+        }
+        Poll::Pending
+    }
+}
+
+impl<R, const EFFECT: Effects> Default for Select<'_, R, EFFECT> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A single operation in a `Select`.
+trait Operation<R> {
+    /// Attempts the operation, registering for a wakeup through `cx` if it can't
+    /// complete yet. Only a `TRY` send can fail to allocate.
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<R, AllocError>>;
+
+    /// Offers the message of a send on a rendezvous channel to receivers, to be
+    /// taken while the `Select` waits.
+    fn offer(&mut self) {}
+
+    /// Takes back what `offer` offered, or completes the operation if a
+    /// receiver took it already.
+    fn retract(&mut self) -> Option<Result<R, AllocError>> {
+        None
+    }
+}
+
+struct RecvOp<'a, T, F> {
+    chan: &'a Channel<T>,
+    f: Option<F>,
+    listener: Option<EventListener>,
+}
+
+impl<T, R, F: FnOnce(Result<T, RecvError>) -> R> Operation<R> for RecvOp<'_, T, F> {
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<R, AllocError>> {
+        let chan = self.chan;
+        let res = futures_core::ready!(listen::poll_wait(
+            &mut self.listener,
+            &chan.recv_ops,
+            cx,
+            || {
+                match chan.try_recv() {
+                    Ok(t) => Some(Ok(t)),
+                    Err(TryRecvError::Disconnected) => Some(Err(RecvError)),
+                    Err(TryRecvError::Empty) => None,
+                }
+            }
+        ));
+        let f = self.f.take().expect("operation polled after completion");
+        Poll::Ready(Ok(f(res)))
+    }
+}
+
+struct SendOp<'a, T, F> {
+    chan: &'a Channel<T>,
+    msg: Option<T>,
+    /// The ticket of `msg` while it's offered on a rendezvous channel.
+    offered: Option<u64>,
+    f: Option<F>,
+    listener: Option<EventListener>,
+    /// Whether to fail instead of aborting if the queue can't grow, under `TRY`.
+    fallible: bool,
+}
+
+impl<T, R, F: FnOnce(Result<(), SendError<T>>) -> R> Operation<R> for SendOp<'_, T, F> {
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Result<R, AllocError>> {
+        let SendOp {
+            chan,
+            msg,
+            listener,
+            fallible,
+            ..
+        } = self;
+        let res = futures_core::ready!(listen::poll_wait(listener, &chan.send_ops, cx, || {
+            let t = msg.take().expect("operation polled after completion");
+            let res = match *fallible {
+                true => chan.try_send_fallible(t),
+                false => Ok(chan.try_send(t)),
+            };
+            match res {
+                Ok(Ok(())) => Some(Ok(Ok(()))),
+                Ok(Err(TrySendError::Disconnected(t))) => Some(Ok(Err(SendError(t)))),
+                Ok(Err(TrySendError::Full(t))) => {
+                    *msg = Some(t);
+                    None
+                }
+                Err(_) => Some(Err(AllocError)),
+            }
+        }));
+        let f = self.f.take().expect("operation polled after completion");
+        Poll::Ready(res.map(f))
+    }
+
+    fn offer(&mut self) {
+        let Some(msg) = self.msg.take() else {
+            return;
+        };
+        match self.chan.offer(msg) {
+            Ok(ticket) => self.offered = Some(ticket),
+            Err(msg) => self.msg = Some(msg),
+        }
+    }
+
+    fn retract(&mut self) -> Option<Result<R, AllocError>> {
+        let ticket = self.offered.take()?;
+        match self.chan.retract(ticket) {
+            Ok(()) => {
+                let f = self.f.take().expect("operation polled after completion");
+                Some(Ok(f(Ok(()))))
+            }
+            Err(msg) => {
+                self.msg = Some(msg);
+                None
+            }
+        }
+    }
+}
+
+impl<T, F> Drop for SendOp<'_, T, F> {
+    fn drop(&mut self) {
+        if let Some(ticket) = self.offered.take() {
+            let _ = self.chan.retract(ticket);
+        }
+    }
+}
+
+/// Wakes a thread blocked in `block_on`.
+struct Unparker(Thread);
+
+impl Wake for Unparker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Runs `fut` to completion, parking the current thread whenever it's pending.
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let waker = Waker::from(Arc::new(Unparker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(t) = fut.as_mut().poll(&mut cx) {
+            return t;
+        }
+        thread::park();
+    }
+}
+
+mod wait {
+    use super::*;
+
+    /// Unwraps the result of a `Select` without `TRY` sends, which can't fail.
+    fn infallible<R>(res: Result<R, AllocError>) -> R {
+        res.unwrap_or_else(|AllocError| unreachable!("only `TRY` sends fail to allocate"))
+    }
+
+    #[maybe(async)]
+    pub(crate) fn wait<'a, R: 'a>(select: Select<'a, R, EFFECT>) -> R {
+        if ASYNC {
+            async move { infallible(select.run_async().await) }
+        } else {
+            infallible(select.run_blocking())
+        }
+    }
+
+    impl<'a, R: 'a> wait::Helper<'a, R, { Effects::TRY }> for () {
+        type Ret = Result<R, AllocError>;
+        fn act(select: Select<'a, R, { Effects::TRY }>) -> Self::Ret {
+            select.run_blocking()
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! A channel holding a single, latest value.
+//!
+//! Receivers can look at the current value at any time, and wait for the sender
+//! to publish a new one. Values published in between are never seen.
+
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use event_listener::Event;
+use maybe_async_proc_macro::maybe;
+
+use crate::{listen, Effects, RecvError, SendAllocError, SendError};
+
+/// Creates a watch channel holding `initial`.
+///
+/// The initial value counts as already seen by the receiver.
+pub fn watch<T, const EFFECT: Effects>(initial: T) -> (Sender<T, EFFECT>, Receiver<T, EFFECT>) {
+    let shared = Arc::new(Shared {
+        value: Mutex::new(Arc::new(Some(initial))),
+        version: AtomicU64::new(0),
+        closed: AtomicBool::new(false),
+        receivers: AtomicUsize::new(1),
+        changed_ops: Event::new(),
+    });
+    let receiver = Receiver {
+        shared: shared.clone(),
+        seen: AtomicU64::new(0),
+    };
+    (Sender { shared }, receiver)
+}
+
+struct Shared<T> {
+    /// Only locked to swap the value out or take a reference to it, so neither
+    /// `send` nor `borrow` waits on whoever holds a `Ref`.
+    ///
+    /// Always `Some` once published. A `TRY` send allocates the `Arc` empty and
+    /// moves the value in afterwards, so it can hand the value back on failure.
+    value: Mutex<Arc<Option<T>>>,
+    /// Bumped every time a value is published, while holding the lock.
+    version: AtomicU64,
+    closed: AtomicBool,
+    receivers: AtomicUsize,
+    /// Notified when a value is published or the sender is dropped.
+    changed_ops: Event,
+}
+
+impl<T> Shared<T> {
+    fn borrow(&self) -> Ref<'_, T> {
+        Ref(self.value.lock().unwrap().clone(), PhantomData)
+    }
+}
+
+/// A reference to the value of a watch channel at the time it was borrowed.
+///
+/// Holding on to it doesn't stop the sender from publishing new values.
+pub struct Ref<'a, T>(Arc<Option<T>>, PhantomData<&'a T>);
+
+impl<T> Deref for Ref<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        (*self.0).as_ref().unwrap()
+    }
+}
+
+/// The sending side of a watch channel.
+pub struct Sender<T, const EFFECT: Effects> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T, const EFFECT: Effects> Sender<T, EFFECT> {
+    /// Publish a new value, waking every receiver waiting in `changed`.
+    ///
+    /// This never blocks, even while a [`Ref`] to an older value is held.
+    /// Fails if there are no receivers.
+    ///
+    /// Under `TRY`, this gives `t` back in a [`SendAllocError`] instead of
+    /// aborting if there's no memory to hold it.
+    pub fn send<'a>(&'a self, t: T) -> <() as sender::send::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): sender::send::Helper<'a, T, EFFECT>,
+    {
+        <() as sender::send::Helper<'a, T, EFFECT>>::act(self, t)
+    }
+
+    fn has_receivers(&self) -> bool {
+        self.shared.receivers.load(Ordering::SeqCst) > 0
+    }
+
+    /// Publishes `t`, aborting if there's no memory to hold it.
+    fn send_infallible(&self, t: T) -> Result<(), SendError<T>> {
+        if !self.has_receivers() {
+            return Err(SendError(t));
+        }
+        self.publish(Arc::new(Some(t)));
+        Ok(())
+    }
+
+    /// Swaps `new` in as the current value.
+    fn publish(&self, mut new: Arc<Option<T>>) {
+        let mut value = self.shared.value.lock().unwrap();
+        mem::swap(&mut *value, &mut new);
+        self.shared.version.fetch_add(1, Ordering::SeqCst);
+        drop(value);
+        // Dropping the old value can run arbitrary code, so not while locked.
+        drop(new);
+        self.shared.changed_ops.notify(usize::MAX);
+    }
+
+    /// Returns a reference to the current value.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.shared.borrow()
+    }
+
+    /// Creates a new receiver, which has already seen the current value.
+    pub fn subscribe(&self) -> Receiver<T, EFFECT> {
+        self.shared.receivers.fetch_add(1, Ordering::SeqCst);
+        Receiver {
+            shared: self.shared.clone(),
+            seen: AtomicU64::new(self.shared.version.load(Ordering::SeqCst)),
+        }
+    }
+
+    /// Returns the number of receivers for the channel.
+    pub fn receiver_count(&self) -> usize {
+        self.shared.receivers.load(Ordering::SeqCst)
+    }
+}
+
+impl<T, const EFFECT: Effects> Drop for Sender<T, EFFECT> {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::SeqCst);
+        self.shared.changed_ops.notify(usize::MAX);
+    }
+}
+
+/// The receiving side of a watch channel.
+pub struct Receiver<T, const EFFECT: Effects> {
+    shared: Arc<Shared<T>>,
+    /// The version of the last value `changed` returned for.
+    seen: AtomicU64,
+}
+
+impl<T, const EFFECT: Effects> Receiver<T, EFFECT> {
+    /// Wait for a value this receiver hasn't seen yet to be published.
+    ///
+    /// Fails once the sender is dropped and there's nothing new left to see.
+    pub fn changed<'a>(&'a self) -> <() as receiver::changed::Helper<'a, T, EFFECT>>::Ret
+    where
+        (): receiver::changed::Helper<'a, T, EFFECT>,
+    {
+        receiver::changed::<T, EFFECT>(self)
+    }
+
+    /// Returns a reference to the current value.
+    ///
+    /// This doesn't mark the value as seen.
+    pub fn borrow(&self) -> Ref<'_, T> {
+        self.shared.borrow()
+    }
+
+    /// Marks the latest value as seen, or returns `None` if there's nothing to report yet.
+    fn try_changed(&self) -> Option<Result<(), RecvError>> {
+        let version = self.shared.version.load(Ordering::SeqCst);
+        if self.seen.swap(version, Ordering::SeqCst) != version {
+            return Some(Ok(()));
+        }
+        if self.shared.closed.load(Ordering::SeqCst) {
+            return Some(Err(RecvError));
+        }
+        None
+    }
+
+    fn changed_blocking(&self) -> Result<(), RecvError> {
+        let changed = listen::wait_blocking(&self.shared.changed_ops, None, || self.try_changed());
+        changed.expect("waited without a deadline")
+    }
+
+    async fn changed_async(&self) -> Result<(), RecvError> {
+        let changed = listen::wait_async(&self.shared.changed_ops, None, || self.try_changed());
+        changed.await.expect("waited without a deadline")
+    }
+}
+
+impl<T, const EFFECT: Effects> Clone for Receiver<T, EFFECT> {
+    fn clone(&self) -> Self {
+        self.shared.receivers.fetch_add(1, Ordering::SeqCst);
+        Self {
+            shared: self.shared.clone(),
+            seen: AtomicU64::new(self.seen.load(Ordering::SeqCst)),
+        }
+    }
+}
+
+impl<T, const EFFECT: Effects> Drop for Receiver<T, EFFECT> {
+    fn drop(&mut self) {
+        self.shared.receivers.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+mod sender {
+    use super::*;
+
+    /// Publishing never waits, so `send` is the same in both modes, and only
+    /// differs under `TRY`.
+    pub(crate) mod send {
+        use super::*;
+
+        /// Support trait for `Sender::send`.
+        pub trait Helper<'a, T, const EFFECT: Effects> {
+            /// What is the type `send` is returning
+            type Ret;
+            fn act(sender: &'a Sender<T, EFFECT>, t: T) -> Self::Ret;
+        }
+
+        impl<'a, T: 'a> Helper<'a, T, { Effects::NONE }> for () {
+            type Ret = Result<(), SendError<T>>;
+            fn act(sender: &'a Sender<T, { Effects::NONE }>, t: T) -> Self::Ret {
+                sender.send_infallible(t)
+            }
+        }
+
+        impl<'a, T: 'a> Helper<'a, T, { Effects::ASYNC }> for () {
+            type Ret = Result<(), SendError<T>>;
+            fn act(sender: &'a Sender<T, { Effects::ASYNC }>, t: T) -> Self::Ret {
+                sender.send_infallible(t)
+            }
+        }
+
+        impl<'a, T: 'a> Helper<'a, T, { Effects::TRY }> for () {
+            type Ret = Result<Result<(), SendError<T>>, SendAllocError<T>>;
+            fn act(sender: &'a Sender<T, { Effects::TRY }>, t: T) -> Self::Ret {
+                if !sender.has_receivers() {
+                    return Ok(Err(SendError(t)));
+                }
+                let Ok(mut new) = Arc::try_new(None) else {
+                    return Err(SendAllocError(t));
+                };
+                *Arc::get_mut(&mut new).unwrap() = Some(t);
+                sender.publish(new);
+                Ok(Ok(()))
+            }
+        }
+    }
+}
+
+mod receiver {
+    use super::*;
+
+    #[maybe(async)]
+    pub(crate) fn changed<'a, T: 'a>(receiver: &'a Receiver<T, EFFECT>) -> Result<(), RecvError> {
+        if ASYNC {
+            async move { receiver.changed_async().await }
+        } else {
+            receiver.changed_blocking()
+        }
+    }
+
+    impl<'a, T: 'a> changed::Helper<'a, T, { Effects::TRY }> for () {
+        type Ret = Result<(), RecvError>;
+        fn act(receiver: &'a Receiver<T, { Effects::TRY }>) -> Self::Ret {
+            receiver.changed_blocking()
+        }
+    }
+}
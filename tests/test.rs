@@ -1,10 +1,51 @@
-#![feature(const_waker)]
+#![feature(const_waker, async_iterator)]
+#![feature(adt_const_params, type_alias_impl_trait)]
+#![feature(allocator_api, try_blocks, try_trait_v2)]
+#![allow(incomplete_features)]
 
+use futures_lite::future::{block_on, poll_once, zip};
 use maybe_async_channel::*;
+use maybe_async_proc_macro::maybe;
+use std::alloc::{AllocError, GlobalAlloc, Layout, System};
+use std::cell::Cell;
 use std::future::Future;
-use std::pin::pin;
+use std::pin::{pin, Pin};
 use std::ptr;
+use std::sync::{Arc, OnceLock};
 use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+use std::time::{Duration, Instant};
+
+thread_local! {
+    /// Allocations bigger than this many bytes fail on this thread.
+    static ALLOC_LIMIT: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+/// Fails allocations over `ALLOC_LIMIT`, to see what happens when memory runs out.
+struct LimitedAlloc;
+
+unsafe impl GlobalAlloc for LimitedAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match ALLOC_LIMIT.try_with(Cell::get) {
+            Ok(limit) if layout.size() > limit => ptr::null_mut(),
+            _ => unsafe { System.alloc(layout) },
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: LimitedAlloc = LimitedAlloc;
+
+/// Runs `f` with allocations bigger than `limit` bytes failing.
+fn with_alloc_limit<R>(limit: usize, f: impl FnOnce() -> R) -> R {
+    ALLOC_LIMIT.with(|cell| cell.set(limit));
+    let res = f();
+    ALLOC_LIMIT.with(|cell| cell.set(usize::MAX));
+    res
+}
 
 fn run_to_completion<T>(f: impl Future<Output = T>) -> T {
     const WAKER: &Waker = {
@@ -30,36 +71,1497 @@ fn run_to_completion<T>(f: impl Future<Output = T>) -> T {
 
 #[test]
 fn sync_call() {
-    let (mut sender, _receiver) = bounded::<usize, false>(10);
+    let (sender, _receiver) = bounded::<usize, { Effects::NONE }>(10);
     sender.send(42).unwrap();
 }
 
 #[test]
 fn async_call() {
     run_to_completion(async {
-        let (mut sender, _receiver) = bounded::<usize, true>(42);
+        let (sender, _receiver) = bounded::<usize, { Effects::ASYNC }>(42);
         sender.send(42).await.unwrap();
     });
 }
 
 #[test]
 fn sync_recv() {
-    let (mut sender, mut receiver) = bounded::<usize, false>(10);
+    let (sender, mut receiver) = bounded::<usize, { Effects::NONE }>(10);
     sender.send(42).unwrap();
     drop(sender);
-    while let Some(elem) = receiver.next() {
+    while let Some(elem) = Iterator::<{ Effects::NONE }>::next(&mut receiver) {
         assert_eq!(elem, 42);
     }
+    assert_eq!(Iterator::<{ Effects::NONE }>::next(&mut &receiver), None);
 }
 
 #[test]
 fn async_recv() {
     run_to_completion(async {
-        let (mut sender, mut receiver) = bounded::<usize, true>(42);
+        let (sender, mut receiver) = bounded::<usize, { Effects::ASYNC }>(42);
         sender.send(42).await.unwrap();
         drop(sender);
         while let Some(elem) = receiver.next().await {
             assert_eq!(elem, 42);
         }
+        assert_eq!(
+            Iterator::<{ Effects::ASYNC }>::next(&mut &receiver).await,
+            None
+        );
+    });
+}
+
+#[test]
+fn sync_unbounded_call() {
+    let (sender, _receiver) = unbounded::<usize, { Effects::NONE }>();
+    sender.send(42).unwrap();
+}
+
+#[test]
+fn async_unbounded_call() {
+    run_to_completion(async {
+        let (sender, _receiver) = unbounded::<usize, { Effects::ASYNC }>();
+        sender.send(42).await.unwrap();
+    });
+}
+
+#[test]
+fn sync_unbounded_recv() {
+    let (sender, mut receiver) = unbounded::<usize, { Effects::NONE }>();
+    for n in 0..100 {
+        sender.send(n).unwrap();
+    }
+    drop(sender);
+    let mut expected = 0;
+    while let Some(elem) = Iterator::<{ Effects::NONE }>::next(&mut receiver) {
+        assert_eq!(elem, expected);
+        expected += 1;
+    }
+    assert_eq!(expected, 100);
+}
+
+#[test]
+fn async_unbounded_recv() {
+    run_to_completion(async {
+        let (sender, mut receiver) = unbounded::<usize, { Effects::ASYNC }>();
+        for n in 0..100 {
+            sender.send(n).await.unwrap();
+        }
+        drop(sender);
+        let mut expected = 0;
+        while let Some(elem) = receiver.next().await {
+            assert_eq!(elem, expected);
+            expected += 1;
+        }
+        assert_eq!(expected, 100);
+    });
+}
+
+#[test]
+fn sync_try_send_recv() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(1);
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+    sender.try_send(1).unwrap();
+    assert_eq!(sender.try_send(2), Err(TrySendError::Full(2)));
+    assert_eq!(receiver.try_recv(), Ok(1));
+    drop(sender);
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+}
+
+#[test]
+fn async_try_send_recv() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(1);
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+    sender.try_send(1).unwrap();
+    assert_eq!(sender.try_send(2), Err(TrySendError::Full(2)));
+    assert_eq!(receiver.try_recv(), Ok(1));
+    drop(receiver);
+    assert_eq!(sender.try_send(3), Err(TrySendError::Disconnected(3)));
+}
+
+#[test]
+fn sync_recv_method() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(10);
+    sender.send(42).unwrap();
+    drop(sender);
+    assert_eq!(receiver.recv(), Ok(42));
+    assert_eq!(receiver.recv(), Err(RecvError));
+}
+
+#[test]
+fn async_recv_method() {
+    run_to_completion(async {
+        let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(10);
+        sender.send(42).await.unwrap();
+        drop(sender);
+        assert_eq!(receiver.recv().await, Ok(42));
+        assert_eq!(receiver.recv().await, Err(RecvError));
+    });
+}
+
+#[test]
+fn sync_send_error() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(10);
+    drop(receiver);
+    assert_eq!(sender.send(42), Err(SendError(42)));
+}
+
+#[test]
+fn async_send_error() {
+    run_to_completion(async {
+        let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(10);
+        drop(receiver);
+        assert_eq!(sender.send(42).await, Err(SendError(42)));
+    });
+}
+
+#[test]
+fn sync_mpmc() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(4);
+    let producers: Vec<_> = (0..4)
+        .map(|_| {
+            let sender = sender.clone();
+            std::thread::spawn(move || {
+                for n in 0..100 {
+                    sender.send(n).unwrap();
+                }
+            })
+        })
+        .collect();
+    drop(sender);
+    let consumers: Vec<_> = (0..4)
+        .map(|_| {
+            let receiver = receiver.clone();
+            std::thread::spawn(move || {
+                let mut sum = 0;
+                while let Ok(n) = receiver.recv() {
+                    sum += n;
+                }
+                sum
+            })
+        })
+        .collect();
+    drop(receiver);
+    for producer in producers {
+        producer.join().unwrap();
+    }
+    let sum: usize = consumers.into_iter().map(|c| c.join().unwrap()).sum();
+    assert_eq!(sum, 4 * (0..100).sum::<usize>());
+}
+
+#[test]
+fn async_mpmc() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(4);
+    let produce = |sender: Sender<usize, { Effects::ASYNC }>| async move {
+        for n in 0..100 {
+            sender.send(n).await.unwrap();
+        }
+    };
+    let consume = |receiver: Receiver<usize, { Effects::ASYNC }>| async move {
+        let mut sum = 0;
+        while let Ok(n) = receiver.recv().await {
+            sum += n;
+        }
+        sum
+    };
+    let producers = zip(produce(sender.clone()), produce(sender));
+    let consumers = zip(consume(receiver.clone()), consume(receiver));
+    let (_, (a, b)) = block_on(zip(producers, consumers));
+    assert_eq!(a + b, 2 * (0..100).sum::<usize>());
+}
+
+#[test]
+fn sync_timeouts() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(1);
+    let timeout = Duration::from_millis(10);
+    assert_eq!(
+        receiver.recv_timeout(timeout),
+        Err(RecvTimeoutError::Timeout)
+    );
+    sender.send(1).unwrap();
+    assert_eq!(
+        sender.send_timeout(2, timeout),
+        Err(SendTimeoutError::Timeout(2))
+    );
+    assert_eq!(receiver.recv_deadline(Instant::now() + timeout), Ok(1));
+    sender.send_deadline(3, Instant::now() + timeout).unwrap();
+    drop(sender);
+    assert_eq!(receiver.recv_timeout(timeout), Ok(3));
+    assert_eq!(
+        receiver.recv_timeout(timeout),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn async_timeouts() {
+    block_on(async {
+        let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(1);
+        let timeout = Duration::from_millis(10);
+        assert_eq!(
+            receiver.recv_timeout(timeout).await,
+            Err(RecvTimeoutError::Timeout)
+        );
+        sender.send(1).await.unwrap();
+        assert_eq!(
+            sender.send_timeout(2, timeout).await,
+            Err(SendTimeoutError::Timeout(2))
+        );
+        assert_eq!(
+            receiver.recv_deadline(Instant::now() + timeout).await,
+            Ok(1)
+        );
+        sender
+            .send_deadline(3, Instant::now() + timeout)
+            .await
+            .unwrap();
+        drop(sender);
+        assert_eq!(receiver.recv_timeout(timeout).await, Ok(3));
+        assert_eq!(
+            receiver.recv_timeout(timeout).await,
+            Err(RecvTimeoutError::Disconnected)
+        );
+    });
+}
+
+#[test]
+fn async_send_timeout_wakes_on_recv() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(1);
+    sender.try_send(1).unwrap();
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        let n = block_on(receiver.recv()).unwrap();
+        (n, receiver)
+    });
+    block_on(sender.send_timeout(2, Duration::from_secs(10))).unwrap();
+    let (n, receiver) = handle.join().unwrap();
+    assert_eq!(n, 1);
+    assert_eq!(receiver.try_recv(), Ok(2));
+}
+
+#[test]
+fn async_send_timeout_disconnects() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(1);
+    sender.try_send(1).unwrap();
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        drop(receiver);
+    });
+    assert_eq!(
+        block_on(sender.send_timeout(2, Duration::from_secs(10))),
+        Err(SendTimeoutError::Disconnected(2))
+    );
+    handle.join().unwrap();
+}
+
+#[test]
+fn sync_introspection() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(2);
+    assert_eq!(sender.capacity(), Some(2));
+    assert!(receiver.is_empty());
+    sender.try_send(1).unwrap();
+    sender.try_send(2).unwrap();
+    assert_eq!(receiver.len(), 2);
+    assert!(sender.is_full());
+    let receiver2 = receiver.clone();
+    assert_eq!(sender.sender_count(), 1);
+    assert_eq!(sender.receiver_count(), 2);
+    assert!(!sender.is_closed());
+    drop(receiver);
+    drop(receiver2);
+    assert_eq!(sender.receiver_count(), 0);
+    assert!(sender.is_closed());
+
+    let (sender, _receiver) = unbounded::<usize, { Effects::NONE }>();
+    assert_eq!(sender.capacity(), None);
+    assert!(!sender.is_full());
+}
+
+#[test]
+fn async_introspection() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(2);
+    assert_eq!(sender.capacity(), Some(2));
+    assert!(receiver.is_empty());
+    sender.try_send(1).unwrap();
+    sender.try_send(2).unwrap();
+    assert_eq!(receiver.len(), 2);
+    assert!(sender.is_full());
+    let receiver2 = receiver.clone();
+    assert_eq!(sender.sender_count(), 1);
+    assert_eq!(sender.receiver_count(), 2);
+    assert!(!sender.is_closed());
+    drop(receiver);
+    drop(receiver2);
+    assert_eq!(sender.receiver_count(), 0);
+    assert!(sender.is_closed());
+
+    let (sender, _receiver) = unbounded::<usize, { Effects::ASYNC }>();
+    assert_eq!(sender.capacity(), None);
+    assert!(!sender.is_full());
+}
+
+#[test]
+fn sync_close() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(4);
+    sender.send(1).unwrap();
+    sender.send(2).unwrap();
+    assert!(sender.close());
+    assert!(!sender.close());
+    assert!(!receiver.close());
+    assert!(receiver.is_closed());
+    assert_eq!(sender.try_send(3), Err(TrySendError::Disconnected(3)));
+    assert_eq!(receiver.recv(), Ok(1));
+    assert_eq!(receiver.recv(), Ok(2));
+    assert_eq!(receiver.recv(), Err(RecvError));
+}
+
+#[test]
+fn async_close() {
+    run_to_completion(async {
+        let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(4);
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+        assert!(sender.close());
+        assert!(!sender.close());
+        assert!(!receiver.close());
+        assert!(receiver.is_closed());
+        assert_eq!(sender.try_send(3), Err(TrySendError::Disconnected(3)));
+        assert_eq!(receiver.recv().await, Ok(1));
+        assert_eq!(receiver.recv().await, Ok(2));
+        assert_eq!(receiver.recv().await, Err(RecvError));
+    });
+}
+
+#[test]
+fn sync_close_wakes_blocked() {
+    let (_sender, receiver) = bounded::<usize, { Effects::NONE }>(1);
+    let receiver2 = receiver.clone();
+    let handle = std::thread::spawn(move || receiver.recv());
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(receiver2.close());
+    assert_eq!(handle.join().unwrap(), Err(RecvError));
+
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(1);
+    sender.send(1).unwrap();
+    let handle = std::thread::spawn(move || sender.send(2));
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(receiver.close());
+    assert_eq!(handle.join().unwrap(), Err(SendError(2)));
+}
+
+#[test]
+fn async_close_wakes_blocked() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(1);
+    let handle = std::thread::spawn(move || block_on(receiver.recv()));
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(sender.close());
+    assert_eq!(handle.join().unwrap(), Err(RecvError));
+}
+
+#[test]
+fn sync_shared_halves() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(4);
+    let (sender, receiver) = (Arc::new(sender), Arc::new(receiver));
+    let handles: Vec<_> = (0..4)
+        .map(|n| {
+            let sender = sender.clone();
+            std::thread::spawn(move || sender.send(n).unwrap())
+        })
+        .collect();
+    let mut received: Vec<_> = (0..4).map(|_| (&*receiver).next().unwrap()).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    received.sort();
+    assert_eq!(received, [0, 1, 2, 3]);
+}
+
+#[test]
+fn async_shared_halves() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(4);
+    let (sender, receiver) = (Arc::new(sender), Arc::new(receiver));
+    let handles: Vec<_> = (0..4)
+        .map(|n| {
+            let sender = sender.clone();
+            std::thread::spawn(move || block_on(sender.send(n)).unwrap())
+        })
+        .collect();
+    let mut received: Vec<_> = (0..4)
+        .map(|_| block_on((&*receiver).next()).unwrap())
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    received.sort();
+    assert_eq!(received, [0, 1, 2, 3]);
+}
+
+#[test]
+fn sync_std_iter() {
+    let (sender, receiver) = unbounded::<usize, { Effects::NONE }>();
+    for n in 0..4 {
+        sender.send(n).unwrap();
+    }
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [0, 1, 2, 3]);
+    assert_eq!(receiver.try_iter().next(), None);
+
+    for n in 0..4 {
+        sender.send(n).unwrap();
+    }
+    assert_eq!(receiver.iter().take(2).collect::<Vec<_>>(), [0, 1]);
+    let mut received = vec![];
+    drop(sender);
+    for n in &receiver {
+        received.push(n);
+    }
+    assert_eq!(received, [2, 3]);
+
+    let (sender, receiver) = unbounded::<usize, { Effects::NONE }>();
+    std::thread::spawn(move || {
+        for n in 0..4 {
+            sender.send(n).unwrap();
+        }
+    });
+    let doubled: Vec<_> = receiver.map(|n| n * 2).collect();
+    assert_eq!(doubled, [0, 2, 4, 6]);
+}
+
+#[test]
+fn async_iterator() {
+    use std::async_iter::AsyncIterator;
+    use std::future::poll_fn;
+
+    let (sender, mut receiver) = bounded::<usize, { Effects::ASYNC }>(4);
+    let handle = std::thread::spawn(move || {
+        for n in 0..4 {
+            block_on(sender.send(n)).unwrap();
+        }
+    });
+    let mut received = vec![];
+    block_on(async {
+        while let Some(n) = poll_fn(|cx| Pin::new(&mut receiver).poll_next(cx)).await {
+            received.push(n);
+        }
+    });
+    handle.join().unwrap();
+    assert_eq!(received, [0, 1, 2, 3]);
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn async_stream() {
+    use futures_lite::StreamExt;
+
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(4);
+    let handle = std::thread::spawn(move || {
+        for n in 0..4 {
+            block_on(sender.send(n)).unwrap();
+        }
+    });
+    let doubled: Vec<_> = block_on(receiver.map(|n| n * 2).collect());
+    handle.join().unwrap();
+    assert_eq!(doubled, [0, 2, 4, 6]);
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn async_sink() {
+    use futures_sink::Sink;
+    use std::future::poll_fn;
+
+    let (mut sender, receiver) = bounded::<usize, { Effects::ASYNC }>(1);
+    let handle = std::thread::spawn(move || {
+        block_on(async {
+            for n in 0..4 {
+                poll_fn(|cx| Pin::new(&mut sender).poll_ready(cx))
+                    .await
+                    .unwrap();
+                Pin::new(&mut sender).start_send(n).unwrap();
+            }
+            poll_fn(|cx| Pin::new(&mut sender).poll_close(cx))
+                .await
+                .unwrap();
+        })
+    });
+    let mut received = vec![];
+    while let Ok(n) = block_on(receiver.recv()) {
+        received.push(n);
+    }
+    handle.join().unwrap();
+    assert_eq!(received, [0, 1, 2, 3]);
+}
+
+#[cfg(feature = "futures")]
+#[test]
+fn async_sink_back_pressure() {
+    use futures_sink::Sink;
+    use std::future::poll_fn;
+
+    let (mut sender, receiver) = bounded::<usize, { Effects::ASYNC }>(1);
+    block_on(sender.send(0)).unwrap();
+    let mut poll_ready = || {
+        block_on(poll_once(poll_fn(|cx| {
+            Pin::new(&mut sender).poll_ready(cx)
+        })))
+    };
+    assert_eq!(poll_ready(), None);
+    assert_eq!(receiver.try_recv(), Ok(0));
+    assert_eq!(poll_ready(), Some(Ok(())));
+
+    // Without waiting for `poll_ready`, a message which doesn't fit is held on
+    // to, but another one is refused rather than replacing it.
+    Pin::new(&mut sender).start_send(1).unwrap();
+    Pin::new(&mut sender).start_send(2).unwrap();
+    assert_eq!(
+        Pin::new(&mut sender).start_send(3),
+        Err(TrySendError::Full(3))
+    );
+    assert_eq!(receiver.try_recv(), Ok(1));
+    block_on(poll_fn(|cx| Pin::new(&mut sender).poll_flush(cx))).unwrap();
+    assert_eq!(receiver.try_recv(), Ok(2));
+}
+
+#[test]
+fn sync_send_async_recv() {
+    let (sender, receiver) = bounded_mixed::<usize, { Effects::NONE }, { Effects::ASYNC }>(1);
+    let handle = std::thread::spawn(move || {
+        for i in 0..10 {
+            sender.send(i).unwrap();
+        }
+    });
+    block_on(async {
+        for i in 0..10 {
+            assert_eq!(receiver.recv().await, Ok(i));
+        }
+        assert_eq!(receiver.recv().await, Err(RecvError));
+    });
+    handle.join().unwrap();
+}
+
+#[test]
+fn async_send_sync_recv() {
+    let (sender, receiver) = bounded_mixed::<usize, { Effects::ASYNC }, { Effects::NONE }>(1);
+    let handle = std::thread::spawn(move || {
+        block_on(async {
+            for i in 0..10 {
+                sender.send(i).await.unwrap();
+            }
+        })
+    });
+    for i in 0..10 {
+        assert_eq!(receiver.recv(), Ok(i));
+    }
+    assert_eq!(receiver.recv(), Err(RecvError));
+    handle.join().unwrap();
+
+    let (sender, receiver) = unbounded_mixed::<usize, { Effects::ASYNC }, { Effects::NONE }>();
+    assert!(block_on(sender.send(1)).is_ok());
+    assert_eq!(receiver.try_recv(), Ok(1));
+}
+
+#[test]
+fn sync_into_async() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(2);
+    sender.send(1).unwrap();
+    let sender = sender.into_async();
+    let receiver = receiver.into_async();
+    assert_eq!(sender.sender_count(), 1);
+    block_on(async {
+        sender.send(2).await.unwrap();
+        assert_eq!(receiver.recv().await, Ok(1));
+        assert_eq!(receiver.recv().await, Ok(2));
+    });
+    drop(sender);
+    assert_eq!(block_on(receiver.recv()), Err(RecvError));
+}
+
+#[test]
+fn async_into_blocking() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(1);
+    block_on(sender.send(1)).unwrap();
+    let receiver = receiver.into_blocking();
+    let handle = std::thread::spawn(move || {
+        let sender = sender.into_blocking();
+        sender.send(2).unwrap();
+    });
+    assert_eq!(receiver.recv(), Ok(1));
+    assert_eq!(receiver.recv(), Ok(2));
+    handle.join().unwrap();
+    assert_eq!(receiver.recv(), Err(RecvError));
+}
+
+#[test]
+fn sync_oneshot() {
+    let (sender, receiver) = oneshot::<usize, { Effects::NONE }>();
+    let handle = std::thread::spawn(move || sender.send(1));
+    assert_eq!(receiver.recv(), Ok(1));
+    assert_eq!(handle.join().unwrap(), Ok(()));
+
+    let (sender, receiver) = oneshot::<usize, { Effects::NONE }>();
+    drop(sender);
+    assert_eq!(receiver.recv(), Err(Canceled));
+
+    let (sender, receiver) = oneshot::<usize, { Effects::NONE }>();
+    drop(receiver);
+    assert_eq!(sender.send(1), Err(SendError(1)));
+}
+
+#[test]
+fn async_oneshot() {
+    let (sender, receiver) = oneshot::<usize, { Effects::ASYNC }>();
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        sender.send(1)
+    });
+    assert_eq!(block_on(receiver.recv()), Ok(1));
+    assert_eq!(handle.join().unwrap(), Ok(()));
+
+    let (sender, receiver) = oneshot::<usize, { Effects::ASYNC }>();
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        drop(sender);
+    });
+    assert_eq!(block_on(receiver.recv()), Err(Canceled));
+    handle.join().unwrap();
+}
+
+#[test]
+fn sync_broadcast() {
+    let (sender, receiver) = broadcast::<usize, { Effects::NONE }>(2);
+    let receiver2 = sender.subscribe();
+    assert_eq!(sender.receiver_count(), 2);
+    sender.send(1).unwrap();
+    sender.send(2).unwrap();
+    assert_eq!(receiver.recv(), Ok(1));
+    assert_eq!(receiver2.recv(), Ok(1));
+
+    // Late joiners only see what's sent after they subscribe.
+    let receiver3 = sender.subscribe();
+    sender.send(3).unwrap();
+    sender.send(4).unwrap();
+    assert_eq!(receiver3.recv(), Ok(3));
+    assert_eq!(receiver.recv(), Err(broadcast::RecvError::Lagged(1)));
+    assert_eq!(receiver.recv(), Ok(3));
+    assert_eq!(receiver.try_recv(), Ok(4));
+    assert_eq!(receiver.try_recv(), Err(broadcast::TryRecvError::Empty));
+
+    let handle = std::thread::spawn(move || receiver.recv());
+    std::thread::sleep(Duration::from_millis(50));
+    sender.send(5).unwrap();
+    assert_eq!(handle.join().unwrap(), Ok(5));
+
+    drop(sender);
+    assert_eq!(receiver3.recv(), Ok(4));
+    assert_eq!(receiver3.recv(), Ok(5));
+    assert_eq!(receiver3.recv(), Err(broadcast::RecvError::Disconnected));
+}
+
+#[test]
+fn broadcast_drops_outside_lock() {
+    /// Looks at the channel it's sent on when it's dropped.
+    #[derive(Clone)]
+    struct Msg(Arc<OnceLock<broadcast::Sender<Msg, { Effects::NONE }>>>);
+
+    impl Drop for Msg {
+        fn drop(&mut self) {
+            if let Some(sender) = self.0.get() {
+                sender.receiver_count();
+            }
+        }
+    }
+
+    let (sender, _receiver) = broadcast::<Msg, { Effects::NONE }>(1);
+    let chan = Arc::new(OnceLock::new());
+    let _ = chan.set(sender.clone());
+    sender.send(Msg(chan.clone())).unwrap();
+    // Evicts the first message, which must not deadlock when it's dropped.
+    sender.send(Msg(chan)).unwrap();
+}
+
+#[test]
+fn async_broadcast() {
+    let (sender, receiver) = broadcast::<usize, { Effects::ASYNC }>(2);
+    let receiver2 = sender.subscribe();
+    let handle = std::thread::spawn(move || {
+        block_on(async {
+            let mut received = vec![];
+            loop {
+                match receiver2.recv().await {
+                    Ok(t) => received.push(t),
+                    Err(broadcast::RecvError::Lagged(_)) => {}
+                    Err(broadcast::RecvError::Disconnected) => return received,
+                }
+            }
+        })
+    });
+    block_on(async {
+        for i in 0..2 {
+            sender.send(i).unwrap();
+            assert_eq!(receiver.recv().await, Ok(i));
+        }
+        for i in 2..5 {
+            sender.send(i).unwrap();
+        }
+        assert_eq!(receiver.recv().await, Err(broadcast::RecvError::Lagged(1)));
+        assert_eq!(receiver.recv().await, Ok(3));
+    });
+    drop(sender);
+    assert_eq!(block_on(receiver.recv()), Ok(4));
+    assert_eq!(
+        block_on(receiver.recv()),
+        Err(broadcast::RecvError::Disconnected)
+    );
+    let received = handle.join().unwrap();
+    assert_eq!(received.last(), Some(&4));
+}
+
+#[test]
+fn sync_watch() {
+    let (sender, receiver) = watch::<usize, { Effects::NONE }>(0);
+    assert_eq!(*receiver.borrow(), 0);
+    sender.send(1).unwrap();
+    sender.send(2).unwrap();
+    assert_eq!(receiver.changed(), Ok(()));
+    assert_eq!(*receiver.borrow(), 2);
+
+    let receiver2 = receiver.clone();
+    let handle = std::thread::spawn(move || {
+        receiver2.changed().unwrap();
+        *receiver2.borrow()
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    sender.send(3).unwrap();
+    assert_eq!(handle.join().unwrap(), 3);
+
+    // Holding on to a value doesn't get in the way of publishing the next one.
+    let old = sender.borrow();
+    sender.send(4).unwrap();
+    assert_eq!((*old, *receiver.borrow()), (3, 4));
+    drop(old);
+
+    drop(sender);
+    assert_eq!(receiver.changed(), Ok(()));
+    assert_eq!(receiver.changed(), Err(RecvError));
+    assert_eq!(*receiver.borrow(), 4);
+}
+
+#[test]
+fn async_watch() {
+    let (sender, receiver) = watch::<usize, { Effects::ASYNC }>(0);
+    let receiver2 = sender.subscribe();
+    let handle = std::thread::spawn(move || {
+        block_on(async {
+            receiver2.changed().await.unwrap();
+            *receiver2.borrow()
+        })
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    sender.send(1).unwrap();
+    assert_eq!(handle.join().unwrap(), 1);
+    assert_eq!(*sender.borrow(), 1);
+
+    block_on(async {
+        assert_eq!(receiver.changed().await, Ok(()));
+        drop(sender);
+        assert_eq!(receiver.changed().await, Err(RecvError));
+    });
+}
+
+#[test]
+fn sync_select() {
+    use maybe_async_channel::select::Select;
+
+    let (sender1, receiver1) = bounded::<usize, { Effects::NONE }>(1);
+    let (sender2, receiver2) = bounded::<usize, { Effects::NONE }>(1);
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        sender2.send(2).unwrap();
+    });
+    let received = Select::<_, { Effects::NONE }>::new()
+        .recv(&receiver1, |res| (1, res))
+        .recv(&receiver2, |res| (2, res))
+        .wait();
+    assert_eq!(received, (2, Ok(2)));
+    handle.join().unwrap();
+
+    let received = Select::<_, { Effects::NONE }>::new()
+        .recv(&receiver1, |_| "recv")
+        .default(|| "default")
+        .wait();
+    assert_eq!(received, "default");
+
+    let start = Instant::now();
+    let received = Select::<_, { Effects::NONE }>::new()
+        .recv(&receiver1, |_| "recv")
+        .timeout(Duration::from_millis(50), || "timeout")
+        .wait();
+    assert_eq!(received, "timeout");
+    assert!(start.elapsed() >= Duration::from_millis(50));
+
+    sender1.send(1).unwrap();
+    let sent = Select::<_, { Effects::NONE }>::new()
+        .send(&sender1, 3, |res| (1, res))
+        .recv(&receiver2, |_| (2, Ok(())))
+        .wait();
+    assert_eq!(sent, (2, Ok(())));
+    assert_eq!(receiver2.recv(), Err(RecvError));
+}
+
+#[test]
+#[should_panic = "no operations have been added to `Select`"]
+fn empty_select() {
+    use maybe_async_channel::select::Select;
+
+    Select::<(), { Effects::NONE }>::new().wait();
+}
+
+#[test]
+fn async_select() {
+    use maybe_async_channel::select::Select;
+
+    let (sender1, receiver1) = bounded::<usize, { Effects::ASYNC }>(1);
+    let (sender2, _receiver2) = bounded::<usize, { Effects::ASYNC }>(1);
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        block_on(sender1.send(1)).unwrap();
+        sender1
+    });
+    block_on(async {
+        let received = Select::<_, { Effects::ASYNC }>::new()
+            .recv(&receiver1, |res| (1, res))
+            .send(&sender2, 2, |res| {
+                (2, res.map(|()| 0).map_err(|_| RecvError))
+            })
+            .wait()
+            .await;
+        assert_eq!(received, (2, Ok(0)));
+
+        let received = Select::<_, { Effects::ASYNC }>::new()
+            .recv(&receiver1, |res| (1, res))
+            .send(&sender2, 3, |res| {
+                (2, res.map(|()| 0).map_err(|_| RecvError))
+            })
+            .wait()
+            .await;
+        assert_eq!(received, (1, Ok(1)));
+
+        let received = Select::<_, { Effects::ASYNC }>::new()
+            .recv(&receiver1, |_| "recv")
+            .timeout(Duration::from_millis(50), || "timeout")
+            .wait()
+            .await;
+        assert_eq!(received, "timeout");
+    });
+    handle.join().unwrap();
+}
+
+#[test]
+fn sync_rendezvous() {
+    let (sender, receiver) = rendezvous::<usize, { Effects::NONE }>();
+    assert_eq!(sender.capacity(), Some(0));
+    assert_eq!(sender.try_send(1), Err(TrySendError::Full(1)));
+    assert_eq!(
+        sender.send_timeout(1, Duration::from_millis(10)),
+        Err(SendTimeoutError::Timeout(1))
+    );
+
+    // The send can't complete before the receiver shows up.
+    let start = Instant::now();
+    let handle = std::thread::spawn(move || {
+        sender.send(1).unwrap();
+        let sent = start.elapsed();
+        (sender, sent)
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(receiver.is_empty());
+    assert_eq!(receiver.recv(), Ok(1));
+    let (sender, sent) = handle.join().unwrap();
+    assert!(sent >= Duration::from_millis(50));
+
+    // A waiting receiver lets `try_send` hand a message over right away.
+    let handle = std::thread::spawn(move || (receiver.recv(), receiver));
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(sender.try_send(2), Ok(()));
+    let (received, receiver) = handle.join().unwrap();
+    assert_eq!(received, Ok(2));
+
+    // A receiver which gave up doesn't leave room for a message behind.
+    assert_eq!(
+        receiver.recv_timeout(Duration::from_millis(10)),
+        Err(RecvTimeoutError::Timeout)
+    );
+    assert_eq!(sender.try_send(3), Err(TrySendError::Full(3)));
+    assert!(receiver.is_empty());
+
+    // Likewise, a waiting sender lets `try_recv` take its message.
+    let handle = std::thread::spawn(move || sender.send(4));
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(receiver.is_empty());
+    assert_eq!(receiver.try_recv(), Ok(4));
+    assert_eq!(handle.join().unwrap(), Ok(()));
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+}
+
+#[test]
+fn async_rendezvous() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(0);
+    assert_eq!(sender.try_send(1), Err(TrySendError::Full(1)));
+    assert_eq!(
+        block_on(sender.send_timeout(1, Duration::from_millis(10))),
+        Err(SendTimeoutError::Timeout(1))
+    );
+
+    let start = Instant::now();
+    let handle = std::thread::spawn(move || {
+        block_on(sender.send(1)).unwrap();
+        start.elapsed()
+    });
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(block_on(receiver.recv()), Ok(1));
+    assert!(handle.join().unwrap() >= Duration::from_millis(50));
+    assert_eq!(block_on(receiver.recv()), Err(RecvError));
+}
+
+#[test]
+fn select_rendezvous() {
+    use maybe_async_channel::select::Select;
+
+    // A select send is taken by a select receive on the other side.
+    let (sender, receiver) = rendezvous::<usize, { Effects::NONE }>();
+    let (other_sender, other_receiver) = unbounded::<usize, { Effects::NONE }>();
+    let handle = std::thread::spawn(move || {
+        let received = Select::<_, { Effects::NONE }>::new()
+            .recv(&receiver, |res| res)
+            .wait();
+        (received, receiver)
+    });
+    let sent = Select::<_, { Effects::NONE }>::new()
+        .send(&sender, 1, |res| res.map_err(|_| RecvError))
+        .recv(&other_receiver, |res| res.map(drop))
+        .wait();
+    assert_eq!(sent, Ok(()));
+    let (received, receiver) = handle.join().unwrap();
+    assert_eq!(received, Ok(1));
+
+    // And by an async receive.
+    let receiver = receiver.into_async();
+    let handle = std::thread::spawn(move || (block_on(receiver.recv()), receiver));
+    let sent = Select::<_, { Effects::NONE }>::new()
+        .send(&sender, 2, |res| res)
+        .wait();
+    assert_eq!(sent, Ok(()));
+    let (received, receiver) = handle.join().unwrap();
+    assert_eq!(received, Ok(2));
+
+    // A send which loses to another operation takes its message back.
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        other_sender.send(3).unwrap();
+    });
+    let received = Select::<_, { Effects::NONE }>::new()
+        .send(&sender, 4, |_| None)
+        .recv(&other_receiver, |res| res.ok())
+        .wait();
+    assert_eq!(received, Some(3));
+    handle.join().unwrap();
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+}
+
+#[test]
+fn async_rendezvous_cancel() {
+    use maybe_async_channel::select::Select;
+    use std::async_iter::AsyncIterator;
+    use std::future::poll_fn;
+
+    let (sender, mut receiver) = rendezvous::<usize, { Effects::ASYNC }>();
+
+    // A cancelled receive doesn't leave room for a message behind.
+    assert_eq!(block_on(poll_once(receiver.recv())), None);
+    assert_eq!(sender.try_send(1), Err(TrySendError::Full(1)));
+    assert!(receiver.is_empty());
+
+    // Neither does a stream which stopped being polled.
+    let next = poll_fn(|cx| Poll::Ready(Pin::new(&mut receiver).poll_next(cx)));
+    assert!(block_on(next).is_pending());
+    assert_eq!(sender.try_send(2), Err(TrySendError::Full(2)));
+
+    // A cancelled send takes its message back.
+    assert_eq!(block_on(poll_once(sender.send(3))), None);
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+    assert_eq!(
+        block_on(sender.send_timeout(4, Duration::from_millis(10))),
+        Err(SendTimeoutError::Timeout(4))
+    );
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+    // And a receive which lost a select doesn't leave room behind either.
+    let (sender, receiver) = rendezvous::<usize, { Effects::NONE }>();
+    let selected = Select::<_, { Effects::NONE }>::new()
+        .recv(&receiver, |_| false)
+        .timeout(Duration::from_millis(10), || true)
+        .wait();
+    assert!(selected);
+    assert_eq!(sender.try_send(5), Err(TrySendError::Full(5)));
+}
+
+#[test]
+fn sync_priority() {
+    let (sender, receiver) = priority_bounded::<usize, { Effects::NONE }>(3);
+    sender.send(2).unwrap();
+    sender.send(3).unwrap();
+    sender.send(1).unwrap();
+    assert!(sender.is_full());
+    assert_eq!(sender.try_send(4), Err(TrySendError::Full(4)));
+
+    let handle = std::thread::spawn(move || sender.send(5));
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(receiver.recv(), Ok(3));
+    assert_eq!(handle.join().unwrap(), Ok(()));
+    assert_eq!(receiver.iter().collect::<Vec<_>>(), [5, 2, 1]);
+}
+
+#[test]
+fn async_priority() {
+    let (sender, receiver) = priority_bounded::<usize, { Effects::ASYNC }>(2);
+    block_on(async {
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+        assert_eq!(receiver.recv().await, Ok(2));
+        sender.send(3).await.unwrap();
+        assert!(sender.close());
+        assert_eq!(sender.send(4).await, Err(SendError(4)));
+        assert_eq!(receiver.recv().await, Ok(3));
+        assert_eq!(receiver.recv().await, Ok(1));
+        assert_eq!(receiver.recv().await, Err(RecvError));
+    });
+}
+
+/// Sums up everything on `receiver`, passing each message on doubled.
+#[maybe(async)]
+fn forward(receiver: Receiver<usize, EFFECT>, sender: Sender<usize, EFFECT>) -> usize {
+    let mut total = 0;
+    while let Ok(n) = receiver.recv().await {
+        total += n;
+        sender.send(n * 2).await.unwrap();
+    }
+    total
+}
+
+#[test]
+fn sync_maybe_fn() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(3);
+    let (doubled_sender, doubled_receiver) = unbounded();
+    for i in 1..=3 {
+        sender.send(i).unwrap();
+    }
+    drop(sender);
+    assert_eq!(forward(receiver, doubled_sender), 6);
+    assert_eq!(doubled_receiver.iter().collect::<Vec<_>>(), [2, 4, 6]);
+}
+
+#[test]
+fn async_maybe_fn() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(3);
+    let (doubled_sender, doubled_receiver) = unbounded();
+    block_on(async {
+        for i in 1..=3 {
+            sender.send(i).await.unwrap();
+        }
+        drop(sender);
+        assert_eq!(forward(receiver, doubled_sender).await, 6);
+        for i in 1..=3 {
+            assert_eq!(doubled_receiver.recv().await, Ok(i * 2));
+        }
+        assert_eq!(doubled_receiver.recv().await, Err(RecvError));
+    });
+}
+
+/// Sends `0..n` on `sender`, returning how many sends went through.
+#[maybe(try)]
+fn send_range(sender: Sender<usize, EFFECT>, n: usize) -> Result<usize, SendAllocError<usize>> {
+    let mut sent = 0;
+    for i in 0..n {
+        if sender.send(i)?.is_ok() {
+            sent += 1;
+        }
+    }
+    sent
+}
+
+#[test]
+fn try_send_alloc() {
+    let (sender, receiver) = unbounded::<usize, { Effects::TRY }>();
+    assert_eq!(sender.send(1), Ok(Ok(())));
+    assert_eq!(
+        sender.send_timeout(2, Duration::from_millis(10)),
+        Ok(Ok(()))
+    );
+    assert_eq!(receiver.recv(), Ok(1));
+    assert_eq!(receiver.recv_timeout(Duration::from_millis(10)), Ok(2));
+    assert_eq!(send_range(sender.clone(), 3), Ok(3));
+    for i in 0..3 {
+        assert_eq!(receiver.recv(), Ok(i));
+    }
+    drop(receiver);
+    assert_eq!(sender.send(4), Ok(Err(SendError(4))));
+    assert_eq!(send_range(sender, 3), Ok(0));
+
+    let (sender, receiver) = unbounded::<usize, { Effects::NONE }>();
+    assert_eq!(send_range(sender, 3), 3);
+    assert_eq!(receiver.iter().collect::<Vec<_>>(), [0, 1, 2]);
+
+    // With nothing left to allocate, the message comes back.
+    let (sender, receiver) = unbounded::<usize, { Effects::TRY }>();
+    let res = with_alloc_limit(0, || sender.send(1));
+    assert_eq!(res, Err(SendAllocError(1)));
+    let res = with_alloc_limit(0, || sender.send_timeout(2, Duration::from_millis(10)));
+    assert_eq!(res, Err(SendAllocError(2)));
+    let res = with_alloc_limit(0, || sender.try_send(3));
+    assert_eq!(res, Err(SendAllocError(3)));
+    let res = with_alloc_limit(0, || send_range(sender.clone(), 3));
+    assert_eq!(res, Err(SendAllocError(0)));
+    let res = with_alloc_limit(0, || sender.send_all([5, 6]));
+    assert_eq!(res, Err(SendAllocError(5)));
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+    // Without room for a batch, messages go one at a time.
+    let res = with_alloc_limit(64, || sender.send_all([7, 8]));
+    assert_eq!(res, Ok(Ok(())));
+    let mut out = vec![];
+    assert_eq!(receiver.recv_many(&mut out, 2), Ok(Ok(2)));
+    assert_eq!(out, [7, 8]);
+}
+
+#[test]
+fn try_recv_many_alloc() {
+    let (sender, receiver) = unbounded::<usize, { Effects::TRY }>();
+    sender.send_all([1, 2, 3]).unwrap().unwrap();
+    let mut out = vec![];
+    let res = with_alloc_limit(0, || receiver.recv_many(&mut out, 3));
+    assert_eq!(res, Err(AllocError));
+    // Only as many as fit are taken, and the rest stay in the channel.
+    let mut out = Vec::with_capacity(2);
+    let res = with_alloc_limit(0, || receiver.recv_many(&mut out, 3));
+    assert_eq!(res, Ok(Ok(2)));
+    assert_eq!(out, [1, 2]);
+    assert_eq!(receiver.recv(), Ok(3));
+}
+
+#[test]
+fn try_watch_alloc() {
+    let (sender, receiver) = watch::<usize, { Effects::TRY }>(0);
+    let res = with_alloc_limit(0, || sender.send(1));
+    assert_eq!(res, Err(SendAllocError(1)));
+    assert_eq!(*receiver.borrow(), 0);
+    assert_eq!(sender.send(2), Ok(Ok(())));
+    assert_eq!(receiver.changed(), Ok(()));
+    assert_eq!(*receiver.borrow(), 2);
+}
+
+#[test]
+fn send_without_allocating() {
+    // These make room for their messages when they're created.
+    let (sender, receiver) = oneshot::<usize, { Effects::TRY }>();
+    assert_eq!(with_alloc_limit(0, || sender.send(1)), Ok(()));
+    assert_eq!(receiver.recv(), Ok(1));
+
+    let (sender, receiver) = broadcast::<usize, { Effects::TRY }>(2);
+    let res = with_alloc_limit(0, || (sender.send(1), sender.send(2), sender.send(3)));
+    assert_eq!(res, (Ok(()), Ok(()), Ok(())));
+    assert_eq!(receiver.recv(), Err(broadcast::RecvError::Lagged(1)));
+
+    let (sender, receiver) = bounded::<usize, { Effects::TRY }>(1);
+    assert_eq!(with_alloc_limit(0, || sender.send(1)), Ok(Ok(())));
+    assert_eq!(receiver.recv(), Ok(1));
+}
+
+#[test]
+fn try_select_alloc() {
+    use maybe_async_channel::select::Select;
+
+    // Big enough that making room for one fails, while waiting's own small
+    // allocations still go through.
+    let (sender, receiver) = unbounded::<[u8; 512], { Effects::TRY }>();
+    let select = Select::<_, { Effects::TRY }>::new().send(&sender, [1; 512], |res| res.is_ok());
+    let res = with_alloc_limit(256, || select.wait());
+    assert_eq!(res, Err(AllocError));
+    assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+    let received = Select::<_, { Effects::TRY }>::new()
+        .send(&sender, [2; 512], |res| res.is_ok())
+        .wait();
+    assert_eq!(received, Ok(true));
+    assert_eq!(receiver.recv(), Ok([2; 512]));
+}
+
+#[test]
+fn try_effect() {
+    use maybe_async_channel::select::Select;
+
+    let (sender, mut receiver) = bounded::<usize, { Effects::TRY }>(4);
+    assert_eq!(sender.send_all([1, 2, 3]), Ok(Ok(())));
+    assert_eq!(
+        sender.send_deadline(4, Instant::now() + Duration::from_millis(10)),
+        Ok(Ok(()))
+    );
+    assert_eq!(Iterator::<{ Effects::TRY }>::next(&mut receiver), Some(1));
+    assert_eq!(Iterator::<{ Effects::TRY }>::next(&mut &receiver), Some(2));
+    assert_eq!(
+        receiver.recv_deadline(Instant::now() + Duration::from_millis(10)),
+        Ok(3)
+    );
+    let mut out = vec![];
+    assert_eq!(receiver.recv_many(&mut out, 4), Ok(Ok(1)));
+    assert_eq!(out, [4]);
+
+    sender.send(5).unwrap().unwrap();
+    let received = Select::<_, { Effects::TRY }>::new()
+        .recv(&receiver, |res| res)
+        .wait();
+    assert_eq!(received, Ok(Ok(5)));
+    drop(sender);
+    assert_eq!(Iterator::<{ Effects::TRY }>::next(&mut receiver), None);
+
+    let (sender, receiver) = oneshot::<usize, { Effects::TRY }>();
+    let handle = std::thread::spawn(move || sender.send(1));
+    assert_eq!(receiver.recv(), Ok(1));
+    assert_eq!(handle.join().unwrap(), Ok(()));
+
+    let (sender, receiver) = watch::<usize, { Effects::TRY }>(0);
+    sender.send(1).unwrap().unwrap();
+    assert_eq!(receiver.changed(), Ok(()));
+    assert_eq!(*receiver.borrow(), 1);
+    drop(sender);
+    assert_eq!(receiver.changed(), Err(RecvError));
+
+    let (sender, receiver) = broadcast::<usize, { Effects::TRY }>(2);
+    sender.send(1).unwrap();
+    assert_eq!(receiver.recv(), Ok(1));
+    drop(sender);
+    assert_eq!(receiver.recv(), Err(broadcast::RecvError::Disconnected));
+}
+
+#[test]
+fn sync_weak_sender() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(2);
+    let weak = sender.downgrade();
+    assert_eq!(sender.sender_count(), 1);
+
+    let upgraded = weak.upgrade().unwrap();
+    assert_eq!(sender.sender_count(), 2);
+    upgraded.send(1).unwrap();
+    drop(upgraded);
+
+    drop(sender);
+    assert!(receiver.is_closed());
+    assert!(weak.upgrade().is_none());
+    assert!(weak.clone().upgrade().is_none());
+    assert_eq!(receiver.recv(), Ok(1));
+    assert_eq!(receiver.recv(), Err(RecvError));
+}
+
+#[test]
+fn async_weak_sender() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(2);
+    let weak = sender.downgrade();
+    block_on(async {
+        weak.upgrade().unwrap().send(1).await.unwrap();
+        assert_eq!(receiver.recv().await, Ok(1));
+        drop(sender);
+        assert_eq!(receiver.recv().await, Err(RecvError));
+    });
+    assert!(weak.upgrade().is_none());
+}
+
+#[test]
+fn sync_send_all_recv_many() {
+    let (sender, receiver) = bounded::<usize, { Effects::NONE }>(2);
+    let handle = std::thread::spawn(move || {
+        sender.send_all(0..5).unwrap();
+        sender
+    });
+    let mut out = Vec::new();
+    while out.len() < 5 {
+        assert!(receiver.recv_many(&mut out, 4).unwrap() >= 1);
+    }
+    assert_eq!(out, [0, 1, 2, 3, 4]);
+    let sender = handle.join().unwrap();
+
+    sender.send_all([5, 6]).unwrap();
+    assert_eq!(receiver.recv_many(&mut out, 1), Ok(1));
+    assert_eq!(receiver.recv_many(&mut out, 0), Ok(0));
+    assert_eq!(receiver.recv_many(&mut out, 4), Ok(1));
+    assert_eq!(out, [0, 1, 2, 3, 4, 5, 6]);
+
+    // Messages go out while the iterator is still being read, rather than
+    // after all of it has been.
+    let (sender, receiver) = unbounded::<usize, { Effects::NONE }>();
+    let mut read = 0;
+    let msgs = std::iter::from_fn(|| {
+        read += 1;
+        receiver.is_empty().then_some(read)
+    });
+    sender.send_all(msgs.take(10_000)).unwrap();
+    assert!(receiver.len() < 10_000);
+
+    drop(receiver);
+    assert_eq!(sender.send_all(7..10), Err(SendError(7)));
+}
+
+#[test]
+fn async_send_all_recv_many() {
+    let (sender, receiver) = bounded::<usize, { Effects::ASYNC }>(2);
+    let start = Instant::now();
+    let handle = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        block_on(sender.send_all(0..5)).unwrap();
+    });
+    let mut out = Vec::new();
+    block_on(async {
+        assert!(receiver.recv_many(&mut out, 4).await.unwrap() >= 1);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        while receiver.recv_many(&mut out, 4).await.is_ok() {}
+    });
+    handle.join().unwrap();
+    assert_eq!(out, [0, 1, 2, 3, 4]);
+
+    let (sender, receiver) = unbounded::<usize, { Effects::ASYNC }>();
+    let mut read = 0;
+    let msgs = std::iter::from_fn(|| {
+        read += 1;
+        receiver.is_empty().then_some(read)
+    });
+    block_on(sender.send_all(msgs.take(10_000))).unwrap();
+    assert!(receiver.len() < 10_000);
+}
+
+#[test]
+fn sync_overflow_policy() {
+    let (sender, receiver) =
+        bounded_with_policy::<usize, { Effects::NONE }>(2, OverflowPolicy::DropOldest);
+    assert_eq!(sender.send(0), Ok(None));
+    assert_eq!(sender.send(1), Ok(None));
+    for i in 2..6 {
+        assert_eq!(sender.send(i), Ok(Some(i - 2)));
+    }
+    assert_eq!(sender.dropped_count(), 4);
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [4, 5]);
+
+    let (sender, receiver) =
+        bounded_with_policy::<usize, { Effects::NONE }>(2, OverflowPolicy::DropNewest);
+    assert_eq!(sender.send(0), Ok(None));
+    assert_eq!(sender.send(1), Ok(None));
+    assert_eq!(sender.send(2), Ok(Some(2)));
+    assert_eq!(sender.dropped_count(), 1);
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [0, 1]);
+
+    let (sender, receiver) =
+        bounded_with_policy::<usize, { Effects::NONE }>(1, OverflowPolicy::Error);
+    assert_eq!(sender.send(0), Ok(None));
+    assert_eq!(sender.send(1), Err(TrySendError::Full(1)));
+    assert_eq!(
+        sender.send(1).unwrap_err().to_string(),
+        "sending on a full channel"
+    );
+    assert_eq!(sender.dropped_count(), 0);
+    assert_eq!(receiver.try_iter().collect::<Vec<_>>(), [0]);
+    drop(receiver);
+    assert_eq!(sender.send(2), Err(TrySendError::Disconnected(2)));
+
+    let (sender, receiver) =
+        bounded_with_policy::<usize, { Effects::NONE }>(1, OverflowPolicy::Block);
+    assert_eq!(sender.send(0), Ok(None));
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            assert_eq!(receiver.recv(), Ok(0));
+        });
+        assert_eq!(sender.send(1), Ok(None));
+    });
+    drop(receiver);
+    assert_eq!(sender.send(2), Err(TrySendError::Disconnected(2)));
+}
+
+#[test]
+fn async_overflow_policy() {
+    let (sender, receiver) =
+        bounded_with_policy::<usize, { Effects::ASYNC }>(2, OverflowPolicy::DropOldest);
+    block_on(async {
+        for i in 0..5 {
+            assert_eq!(sender.send(i).await, Ok(i.checked_sub(2)));
+        }
+        assert_eq!(sender.dropped_count(), 3);
+        assert_eq!(receiver.recv().await, Ok(3));
+        assert_eq!(receiver.recv().await, Ok(4));
     });
+
+    let (sender, receiver) =
+        bounded_with_policy::<usize, { Effects::ASYNC }>(1, OverflowPolicy::Error);
+    block_on(async {
+        assert_eq!(sender.send(0).await, Ok(None));
+        assert_eq!(sender.send(1).await, Err(TrySendError::Full(1)));
+        assert_eq!(receiver.recv().await, Ok(0));
+        assert_eq!(sender.send(2).await, Ok(None));
+        assert_eq!(receiver.recv().await, Ok(2));
+    });
+
+    let (sender, receiver) =
+        bounded_with_policy::<usize, { Effects::ASYNC }>(1, OverflowPolicy::Block);
+    block_on(async {
+        assert_eq!(sender.send(0).await, Ok(None));
+        let (sent, received) = zip(sender.send(1), async {
+            let received = receiver.recv().await;
+            assert_eq!(receiver.recv().await, Ok(1));
+            received
+        })
+        .await;
+        assert_eq!(sent, Ok(None));
+        assert_eq!(received, Ok(0));
+    });
+}
+
+#[test]
+fn overflow_sender_handles() {
+    let (sender, receiver) =
+        bounded_with_policy::<usize, { Effects::NONE }>(1, OverflowPolicy::Block);
+    assert_eq!(sender.capacity(), Some(1));
+    assert_eq!(sender.try_send(0), Ok(None));
+    assert!(sender.is_full());
+    assert_eq!(sender.try_send(1), Err(TrySendError::Full(1)));
+
+    let weak = sender.downgrade();
+    let upgraded = weak.upgrade().unwrap();
+    assert_eq!(sender.sender_count(), 2);
+    drop(sender);
+    let sender = upgraded.into_async();
+    assert_eq!(receiver.recv(), Ok(0));
+    assert_eq!(block_on(sender.send(2)), Ok(None));
+    drop(sender);
+    assert!(weak.upgrade().is_none());
+    assert_eq!(receiver.recv(), Ok(2));
+    assert_eq!(receiver.recv(), Err(RecvError));
+
+    let (sender, _receiver) =
+        bounded_with_policy::<usize, { Effects::ASYNC }>(1, OverflowPolicy::DropOldest);
+    assert_eq!(sender.try_send(0), Ok(None));
+    assert_eq!(sender.try_send(1), Ok(Some(0)));
+    assert_eq!(sender.into_blocking().send(2), Ok(Some(1)));
+}
+
+#[test]
+#[should_panic(expected = "capacity cannot be zero")]
+fn overflow_policy_zero_capacity() {
+    bounded_with_policy::<usize, { Effects::ASYNC }>(0, OverflowPolicy::DropNewest);
+}
+
+#[test]
+fn try_overflow_policy() {
+    let (sender, receiver) =
+        bounded_with_policy::<usize, { Effects::TRY }>(4, OverflowPolicy::DropNewest);
+    with_alloc_limit(0, || assert_eq!(sender.send(0), Err(SendAllocError(0))));
+    for i in 0..4 {
+        assert_eq!(sender.send(i), Ok(Ok(None)));
+    }
+    with_alloc_limit(0, || assert_eq!(sender.send(4), Ok(Ok(Some(4)))));
+    with_alloc_limit(0, || assert_eq!(sender.try_send(5), Ok(Ok(Some(5)))));
+    assert_eq!(sender.dropped_count(), 2);
+    assert_eq!(receiver.len(), 4);
 }